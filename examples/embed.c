@@ -0,0 +1,52 @@
+/*
+ * Embedding Oak in C. Build the library with `cargo build`, then:
+ *
+ *   cc -Iinclude examples/embed.c -Ltarget/debug -loak -o embed
+ *   LD_LIBRARY_PATH=target/debug ./embed
+ */
+#include <stdio.h>
+
+#include "oak.h"
+
+/* An Oak builtin that adds up its two arguments and counts its calls. */
+static int add(void *user_data, size_t argc, const OakValue *const *argv, OakValue **result) {
+    int *calls = user_data;
+    (*calls)++;
+    (void)argc; /* the interpreter checks that there are two */
+    if (oak_value_type(argv[0]) != OAK_NUMBER || oak_value_type(argv[1]) != OAK_NUMBER) {
+        *result = oak_value_string("add takes two numbers");
+        return OAK_ERROR;
+    }
+    *result = oak_value_number(oak_value_as_number(argv[0]) + oak_value_as_number(argv[1]));
+    return OAK_OK;
+}
+
+int main(void) {
+    OakInterpreter *oak = oak_interpreter_new();
+    int calls = 0;
+    oak_register(oak, "add", 2, add, &calls);
+
+    OakValue *result;
+    if (oak_eval(oak, "(def .'greet' /name .(+ 'Hello, ' name)) (add 40 2)", &result) != OAK_OK) {
+        fprintf(stderr, "error: %s\n", oak_error_message(oak));
+        return 1;
+    }
+    printf("%g\n", oak_value_as_number(result));
+    oak_value_free(result);
+
+    OakValue *name = oak_value_string("C");
+    const OakValue *args[] = {name};
+    if (oak_call(oak, "greet", 1, args, &result) == OAK_OK) {
+        printf("%s\n", oak_value_as_string(result));
+        oak_value_free(result);
+    }
+    oak_value_free(name);
+
+    if (oak_eval(oak, "\n(add 'x' 1)", &result) == OAK_ERROR) {
+        printf("error at line %zu: %s\n", oak_error_line(oak), oak_error_message(oak));
+    }
+    printf("add was called %d times\n", calls);
+
+    oak_interpreter_free(oak);
+    return 0;
+}
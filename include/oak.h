@@ -0,0 +1,246 @@
+/*
+ * The C API for embedding the Oak interpreter. Link against the library built by
+ * `cargo build --release` (liboak.so, liboak.dylib or oak.dll).
+ *
+ * Values and interpreters are opaque, and must be freed with oak_value_free and
+ * oak_interpreter_free. Strings passed in are UTF-8 and copied; strings returned
+ * are borrowed, unless documented otherwise. None of these functions are thread
+ * safe: an interpreter and its values must stay on the thread that created them.
+ *
+ * This header is generated from src/ffi.rs by cbindgen; don't edit it by hand.
+ */
+
+#ifndef OAK_H
+#define OAK_H
+
+#include <stddef.h>
+
+/**
+ * Statuses returned by oak_eval, oak_call, oak_set and oak_register.
+ */
+#define OAK_OK 0
+
+#define OAK_ERROR 1
+
+/**
+ * The program called exit; see oak_exit_code.
+ */
+#define OAK_EXIT 2
+
+/**
+ * Value types, as returned by oak_value_type.
+ */
+#define OAK_NULL 0
+
+#define OAK_NUMBER 1
+
+#define OAK_STRING 2
+
+#define OAK_BOOLEAN 3
+
+#define OAK_FUNCTION 4
+
+#define OAK_ARRAY 5
+
+#define OAK_MAP 6
+
+#define OAK_RECORD 7
+
+#define OAK_VARIANT 8
+
+/**
+ * Capabilities, combined with | for oak_interpreter_new_with_capabilities. The
+ * pure builtins are always available.
+ */
+#define OAK_CAPABILITY_PURE 1
+
+#define OAK_CAPABILITY_CONSOLE 2
+
+#define OAK_CAPABILITY_EXIT 4
+
+#define OAK_CAPABILITY_FILESYSTEM 8
+
+#define OAK_CAPABILITY_ENVIRONMENT 16
+
+#define OAK_CAPABILITY_CLOCK 32
+
+#define OAK_CAPABILITY_RANDOM 64
+
+#define OAK_CAPABILITY_PROCESS 128
+
+typedef struct OakInterpreter OakInterpreter;
+
+typedef struct OakValue OakValue;
+
+/**
+ * A C function registered as an Oak builtin. It is called with the user_data
+ * given to oak_register and the arguments, which are only valid during the call.
+ * It stores a new value in *result (or leaves it NULL, meaning null) and returns
+ * OAK_OK, or returns OAK_ERROR, optionally storing a string value with the error
+ * message in *result. The interpreter frees the stored value.
+ */
+typedef int (*OakCallback)(void *user_data,
+                           size_t argc,
+                           const OakValue *const *argv,
+                           OakValue **result);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * A new interpreter, granting programs the console and exit capabilities (and
+ * the pure builtins).
+ */
+OakInterpreter *oak_interpreter_new(void);
+
+/**
+ * A new interpreter granting the capabilities in the mask, a combination of the
+ * OAK_CAPABILITY_ constants.
+ */
+OakInterpreter *oak_interpreter_new_with_capabilities(unsigned int capabilities);
+
+void oak_interpreter_free(OakInterpreter *interp);
+
+/**
+ * Evaluates a program, storing its value in *out (unless out is NULL) on success
+ * and NULL otherwise. Definitions persist from one call to the next.
+ */
+int oak_eval(OakInterpreter *interp, const char *program, OakValue **out);
+
+/**
+ * Calls the global function with the given name.
+ */
+int oak_call(OakInterpreter *interp,
+             const char *name,
+             size_t argc,
+             const OakValue *const *argv,
+             OakValue **out);
+
+/**
+ * Defines (or replaces) a global. The value is not consumed.
+ */
+int oak_set(OakInterpreter *interp, const char *name, const OakValue *value);
+
+/**
+ * Returns a new value holding the global, or NULL if it is not defined.
+ */
+OakValue *oak_get(const OakInterpreter *interp, const char *name);
+
+/**
+ * Defines a global function taking `arity` arguments which calls the callback.
+ */
+int oak_register(OakInterpreter *interp,
+                 const char *name,
+                 size_t arity,
+                 OakCallback callback,
+                 void *user_data);
+
+/**
+ * The message of the last error, or NULL if the last call on the interpreter
+ * succeeded. It is valid until the next call on the interpreter. A panic inside
+ * the library is an error too, rather than unwinding into C.
+ */
+const char *oak_error_message(const OakInterpreter *interp);
+
+/**
+ * The line of the last error in the program, or 0 if it has no location.
+ */
+size_t oak_error_line(const OakInterpreter *interp);
+
+/**
+ * The column of the last error in the program, or 0 if it has no location.
+ */
+size_t oak_error_column(const OakInterpreter *interp);
+
+/**
+ * The code the program passed to exit, after a call returned OAK_EXIT.
+ */
+int oak_exit_code(const OakInterpreter *interp);
+
+OakValue *oak_value_null(void);
+
+OakValue *oak_value_number(double num);
+
+OakValue *oak_value_integer(long long num);
+
+OakValue *oak_value_boolean(int b);
+
+/**
+ * Copies a UTF-8 string into a new value; returns NULL if it isn't valid UTF-8.
+ */
+OakValue *oak_value_string(const char *st);
+
+/**
+ * Creates an array of the given values, which are not consumed.
+ */
+OakValue *oak_value_array(size_t len, const OakValue *const *items);
+
+void oak_value_free(OakValue *value);
+
+/**
+ * The type of the value: OAK_NULL, OAK_NUMBER and so on.
+ */
+int oak_value_type(const OakValue *value);
+
+/**
+ * The number, or NaN if the value isn't a number. Integers are rounded to the
+ * nearest double.
+ */
+double oak_value_as_number(const OakValue *value);
+
+/**
+ * Stores an integral number in *out and returns OAK_OK, or returns OAK_ERROR if
+ * the value isn't one or doesn't fit in a long long.
+ */
+int oak_value_as_integer(const OakValue *value, long long *out);
+
+/**
+ * 1 for true, 0 for false or anything that isn't a boolean.
+ */
+int oak_value_as_boolean(const OakValue *value);
+
+/**
+ * The contents of a string value, or NULL for other values. It is valid for as
+ * long as the value.
+ */
+const char *oak_value_as_string(const OakValue *value);
+
+/**
+ * The tag of a variant, or NULL for other values. It is valid for as long as the
+ * value.
+ */
+const char *oak_value_tag(const OakValue *value);
+
+/**
+ * The number of elements in an array, entries in a map, fields in a record or
+ * values in the payload of a variant, or 0 for other values.
+ */
+size_t oak_value_length(const OakValue *value);
+
+/**
+ * Returns a new value holding the element of an array (or payload value of a
+ * variant) at the index, or NULL if the index is out of range or the value is
+ * neither.
+ */
+OakValue *oak_value_index(const OakValue *value, size_t index);
+
+/**
+ * Returns a new value holding the entry of a map with the key, or the field of a
+ * record named by a string key, or NULL if there is no such entry or field or
+ * the value is neither.
+ */
+OakValue *oak_value_get(const OakValue *value, const OakValue *key);
+
+/**
+ * The value as Oak prints it, to be freed with oak_string_free.
+ */
+char *oak_value_to_string(const OakValue *value);
+
+void oak_string_free(char *st);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* OAK_H */
@@ -0,0 +1,471 @@
+use crate::check;
+use crate::common::*;
+use crate::expressions::*;
+use crate::infer;
+use std::rc::Rc;
+
+// A parsed Oak program, which can be inspected, checked or formatted without
+// running it.
+pub struct Program {
+    // The `#!` line the source started with, if any
+    shebang: Option<String>,
+    pub(crate) expressions: Vec<Rc<dyn Expression>>,
+}
+
+pub fn parse(program: &str) -> Result<Program, EvalError> {
+    let (shebang, source) = split_shebang(program);
+    let mut str_iter = StringIterator::new(source);
+    let block = BlockExpression::program(&mut str_iter).map_err(|e| e.at(str_iter.location()))?;
+
+    Ok(Program {
+        shebang: shebang.map(String::from),
+        expressions: block.expressions,
+    })
+}
+
+// Splits off a `#!` interpreter line at the start of a program. The newline is
+// left in the source, so that line numbers in error locations stay correct.
+fn split_shebang(program: &str) -> (Option<&str>, &str) {
+    if program.starts_with("#!") {
+        match program.find('\n') {
+            Some(index) => (Some(&program[..index]), &program[index..]),
+            None => (Some(program), ""),
+        }
+    } else {
+        (None, program)
+    }
+}
+
+impl Program {
+    // Runs the static checks, returning every problem found.
+    pub fn check(&self) -> Vec<EvalError> {
+        check::check(&self.expressions)
+    }
+
+    // Infers the types of the program's expressions, returning every conflict
+    // found; see infer.rs.
+    pub fn check_types(&self) -> Vec<EvalError> {
+        infer::check_types(&self.expressions)
+    }
+
+    // Renders the tree as indented text, one expression per line.
+    pub fn to_tree(&self) -> String {
+        let mut out = String::from("Program\n");
+        for expr in &self.expressions {
+            tree_node(&**expr, 1, &mut out);
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let expressions: Vec<String> = self.expressions.iter().map(|e| json_node(&**e)).collect();
+        format!(
+            "{{\"type\":\"program\",\"expressions\":[{}]}}",
+            expressions.join(",")
+        )
+    }
+
+    // Formats the program as source code in the canonical style: two-space
+    // indentation, one expression per line in blocks, and a blank line between
+    // top-level expressions.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        if let Some(shebang) = &self.shebang {
+            out.push_str(shebang);
+            out.push('\n');
+        }
+        let expressions: Vec<String> = self
+            .expressions
+            .iter()
+            .map(|e| source_node(&**e, 0))
+            .collect();
+        out.push_str(&expressions.join("\n\n"));
+        out.push('\n');
+        out
+    }
+}
+
+// The parameters as they are written, with any annotations (`x:number`).
+fn annotated_parameters(fn_obj: &Function) -> Vec<String> {
+    fn_obj
+        .parameters
+        .iter()
+        .enumerate()
+        .map(|(position, name)| match fn_obj.parameter_type(position) {
+            Some(annotation) => format!("{}:{}", name, annotation),
+            None => name.clone(),
+        })
+        .collect()
+}
+
+fn tree_node(expr: &dyn Expression, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    match expr.node() {
+        Node::Literal(literal) => match &*literal.value {
+            Value::Function(fn_obj) => {
+                out.push_str(&format!(
+                    "{}Function ({})\n",
+                    indent,
+                    annotated_parameters(fn_obj).join(" ")
+                ));
+                tree_node(&*fn_obj.body, depth + 1, out);
+            }
+            Value::StringType(st) => {
+                out.push_str(&format!("{}Literal {}\n", indent, json_string(st)))
+            }
+            other => out.push_str(&format!("{}Literal {}\n", indent, other)),
+        },
+        Node::Identifier(id) => out.push_str(&format!("{}Identifier {}\n", indent, id.name)),
+        Node::Block(block) => {
+            out.push_str(&format!("{}Block\n", indent));
+            for expr in &block.expressions {
+                tree_node(&**expr, depth + 1, out);
+            }
+        }
+        Node::Call(call) => {
+            match call.location {
+                Some(location) => out.push_str(&format!("{}Call at {}\n", indent, location)),
+                None => out.push_str(&format!("{}Call\n", indent)),
+            }
+            tree_node(&*call.function, depth + 1, out);
+            for arg in &call.arguments {
+                tree_node(&**arg, depth + 1, out);
+            }
+        }
+        Node::Array(array) => {
+            out.push_str(&format!("{}Array\n", indent));
+            for element in &array.elements {
+                tree_node(&**element, depth + 1, out);
+            }
+        }
+        Node::Map(map) => {
+            out.push_str(&format!("{}Map\n", indent));
+            for (key, value) in &map.entries {
+                out.push_str(&format!("{}  Entry\n", indent));
+                tree_node(&**key, depth + 2, out);
+                tree_node(&**value, depth + 2, out);
+            }
+        }
+        Node::Record(record) => {
+            out.push_str(&format!("{}Record\n", indent));
+            for (name, value) in &record.fields {
+                out.push_str(&format!("{}  Field {}\n", indent, name));
+                tree_node(&**value, depth + 2, out);
+            }
+        }
+        Node::Match(matcher) => {
+            match matcher.location {
+                Some(location) => out.push_str(&format!("{}Match at {}\n", indent, location)),
+                None => out.push_str(&format!("{}Match\n", indent)),
+            }
+            tree_node(&*matcher.subject, depth + 1, out);
+            for arm in &matcher.arms {
+                out.push_str(&format!(
+                    "{}  Arm {}\n",
+                    indent,
+                    source_pattern(&arm.pattern)
+                ));
+                if let Some(guard) = &arm.guard {
+                    out.push_str(&format!("{}    When\n", indent));
+                    tree_node(&**guard, depth + 3, out);
+                }
+                tree_node(&*arm.body, depth + 2, out);
+            }
+        }
+        Node::Native => out.push_str(&format!("{}Native\n", indent)),
+    }
+}
+
+fn json_list(exprs: &[Rc<dyn Expression>]) -> String {
+    let items: Vec<String> = exprs.iter().map(|e| json_node(&**e)).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn json_node(expr: &dyn Expression) -> String {
+    match expr.node() {
+        Node::Literal(literal) => match &*literal.value {
+            Value::Function(fn_obj) => {
+                let parameters: Vec<String> =
+                    fn_obj.parameters.iter().map(|p| json_string(p)).collect();
+                let types: Vec<String> = (0..fn_obj.parameters.len())
+                    .map(|position| match fn_obj.parameter_type(position) {
+                        Some(annotation) => json_string(annotation),
+                        None => "null".to_string(),
+                    })
+                    .collect();
+                format!(
+                    "{{\"type\":\"function\",\"parameters\":[{}],\"types\":[{}],\"body\":{}}}",
+                    parameters.join(","),
+                    types.join(","),
+                    json_node(&*fn_obj.body)
+                )
+            }
+            other => format!("{{\"type\":\"literal\",\"value\":{}}}", other.to_json()),
+        },
+        Node::Identifier(id) => format!(
+            "{{\"type\":\"identifier\",\"name\":{}}}",
+            json_string(&id.name)
+        ),
+        Node::Block(block) => format!(
+            "{{\"type\":\"block\",\"expressions\":{}}}",
+            json_list(&block.expressions)
+        ),
+        Node::Call(call) => {
+            let location = match call.location {
+                Some(location) => format!(
+                    "{{\"line\":{},\"column\":{}}}",
+                    location.line, location.column
+                ),
+                None => "null".to_string(),
+            };
+            format!(
+                "{{\"type\":\"call\",\"function\":{},\"arguments\":{},\"location\":{}}}",
+                json_node(&*call.function),
+                json_list(&call.arguments),
+                location
+            )
+        }
+        Node::Array(array) => format!(
+            "{{\"type\":\"array\",\"elements\":{}}}",
+            json_list(&array.elements)
+        ),
+        Node::Map(map) => {
+            let entries: Vec<String> = map
+                .entries
+                .iter()
+                .map(|(key, value)| format!("[{},{}]", json_node(&**key), json_node(&**value)))
+                .collect();
+            format!("{{\"type\":\"map\",\"entries\":[{}]}}", entries.join(","))
+        }
+        Node::Record(record) => {
+            let fields: Vec<String> = record
+                .fields
+                .iter()
+                .map(|(name, value)| format!("[{},{}]", json_string(name), json_node(&**value)))
+                .collect();
+            format!("{{\"type\":\"record\",\"fields\":[{}]}}", fields.join(","))
+        }
+        Node::Match(matcher) => {
+            let arms: Vec<String> = matcher
+                .arms
+                .iter()
+                .map(|arm| {
+                    let guard = match &arm.guard {
+                        Some(guard) => json_node(&**guard),
+                        None => "null".to_string(),
+                    };
+                    format!(
+                        "{{\"pattern\":{},\"guard\":{},\"body\":{}}}",
+                        json_pattern(&arm.pattern),
+                        guard,
+                        json_node(&*arm.body)
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"type\":\"match\",\"subject\":{},\"arms\":[{}]}}",
+                json_node(&*matcher.subject),
+                arms.join(",")
+            )
+        }
+        Node::Native => "{\"type\":\"native\"}".to_string(),
+    }
+}
+
+fn json_pattern(pattern: &Pattern) -> String {
+    let json_patterns = |patterns: &[Pattern]| {
+        let patterns: Vec<String> = patterns.iter().map(json_pattern).collect();
+        format!("[{}]", patterns.join(","))
+    };
+    match pattern {
+        Pattern::Wildcard => "{\"type\":\"wildcard\"}".to_string(),
+        Pattern::Bind(name) => format!("{{\"type\":\"bind\",\"name\":{}}}", json_string(name)),
+        Pattern::Literal(value) => {
+            format!("{{\"type\":\"literal\",\"value\":{}}}", value.to_json())
+        }
+        Pattern::Variant(tag, payload) => format!(
+            "{{\"type\":\"variant\",\"tag\":{},\"payload\":{}}}",
+            json_string(tag),
+            json_patterns(payload)
+        ),
+        Pattern::Array(elements, rest) => format!(
+            "{{\"type\":\"array\",\"elements\":{},\"rest\":{}}}",
+            json_patterns(elements),
+            rest.as_deref().map_or("null".to_string(), json_string)
+        ),
+        Pattern::Record(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(name, pattern)| format!("[{},{}]", json_string(name), json_pattern(pattern)))
+                .collect();
+            format!("{{\"type\":\"record\",\"fields\":[{}]}}", fields.join(","))
+        }
+    }
+}
+
+// Quotes a string literal, preferring single quotes unless the string contains
+// them (and no double quotes).
+fn quote_string(st: &str) -> String {
+    let quote = if st.contains('\'') && !st.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut out = String::new();
+    out.push(quote);
+    for ch in st.chars() {
+        if ch == quote || ch == '\\' {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out.push(quote);
+    out
+}
+
+// Writes a literal so that it parses back to the same kind of value: floats
+// keep a decimal point and decimals their `d` suffix. NaN and the infinities
+// have no literal (only a host can make them), and are written as null, which
+// the math builtins give in their place.
+fn source_literal(value: &Value) -> String {
+    match value {
+        Value::Number(num) if !num.is_finite() => "null".to_string(),
+        Value::Number(num) if num.fract() == 0.0 => format!("{:.1}", num),
+        Value::Decimal(decimal) => format!("{}d", decimal),
+        other => other.to_string(),
+    }
+}
+
+fn source_list(exprs: &[Rc<dyn Expression>], depth: usize) -> Vec<String> {
+    exprs.iter().map(|e| source_node(&**e, depth)).collect()
+}
+
+fn source_node(expr: &dyn Expression, depth: usize) -> String {
+    match expr.node() {
+        Node::Literal(literal) => match &*literal.value {
+            Value::Function(fn_obj) => {
+                let body = source_node(&*fn_obj.body, depth);
+                if fn_obj.parameters.is_empty() {
+                    format!(".{}", body)
+                } else {
+                    format!("/{} .{}", annotated_parameters(fn_obj).join(" "), body)
+                }
+            }
+            Value::StringType(st) => quote_string(st),
+            Value::Null => "null".to_string(),
+            other => source_literal(other),
+        },
+        Node::Identifier(id) => id.name.clone(),
+        Node::Block(block) => {
+            if block.expressions.is_empty() {
+                return "{}".to_string();
+            }
+            let inner_indent = "  ".repeat(depth + 1);
+            let mut out = String::from("{\n");
+            for expr in source_list(&block.expressions, depth + 1) {
+                out.push_str(&inner_indent);
+                out.push_str(&expr);
+                out.push('\n');
+            }
+            out.push_str(&"  ".repeat(depth));
+            out.push('}');
+            out
+        }
+        Node::Call(call) => {
+            let mut parts = vec![source_node(&*call.function, depth)];
+            parts.extend(source_list(&call.arguments, depth));
+            format!("({})", parts.join(" "))
+        }
+        Node::Array(array) => format!("[{}]", source_list(&array.elements, depth).join(" ")),
+        Node::Map(map) => {
+            let entries: Vec<String> = map
+                .entries
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{} {}",
+                        source_node(&**key, depth),
+                        source_node(&**value, depth)
+                    )
+                })
+                .collect();
+            format!("#{{{}}}", entries.join(" "))
+        }
+        Node::Record(record) => {
+            let fields: Vec<String> = record
+                .fields
+                .iter()
+                .map(|(name, value)| format!("{} {}", name, source_node(&**value, depth)))
+                .collect();
+            format!("#({})", fields.join(" "))
+        }
+        Node::Match(matcher) => {
+            let mut parts = vec!["match".to_string(), source_node(&*matcher.subject, depth)];
+            for arm in &matcher.arms {
+                parts.push(source_pattern(&arm.pattern));
+                if let Some(guard) = &arm.guard {
+                    parts.push(format!("when {}", source_node(&**guard, depth)));
+                }
+                parts.push(format!(".{}", source_node(&*arm.body, depth)));
+            }
+            format!("({})", parts.join(" "))
+        }
+        Node::Native => "<native>".to_string(),
+    }
+}
+
+fn source_pattern(pattern: &Pattern) -> String {
+    let source_patterns =
+        |patterns: &[Pattern]| -> Vec<String> { patterns.iter().map(source_pattern).collect() };
+    match pattern {
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Bind(name) => name.clone(),
+        Pattern::Literal(value) => match &**value {
+            Value::StringType(st) => quote_string(st),
+            Value::Null => "null".to_string(),
+            other => other.to_string(),
+        },
+        Pattern::Variant(tag, payload) if payload.is_empty() => tag.clone(),
+        Pattern::Variant(tag, payload) => {
+            format!("({} {})", tag, source_patterns(payload).join(" "))
+        }
+        Pattern::Array(elements, rest) => {
+            let mut elements = source_patterns(elements);
+            elements.extend(rest.iter().map(|rest| format!("...{}", rest)));
+            format!("[{}]", elements.join(" "))
+        }
+        Pattern::Record(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(name, pattern)| format!("{} {}", name, source_pattern(pattern)))
+                .collect();
+            format!("#({})", fields.join(" "))
+        }
+    }
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn formats_canonically() {
+        let source = "#!/usr/bin/env oak\n(def .'f' /a b .{ (+ a b) \"it's\"   [1 2] })(f 1 2)";
+        let formatted = super::parse(source).unwrap().to_source();
+        assert_eq!(
+            formatted,
+            "#!/usr/bin/env oak\n(def .'f' /a b .{\n  (+ a b)\n  \"it's\"\n  [1 2]\n})\n\n(f 1 2)\n"
+        );
+        assert_eq!(super::parse(&formatted).unwrap().to_source(), formatted);
+
+        // literals keep their kind of number
+        let source = "[2.50d 1.0 -0.5 100.0 1/3 -2/3 -4 -2.5d 0d]";
+        let formatted = super::parse(source).unwrap().to_source();
+        assert_eq!(formatted, "[2.50d 1.0 -0.5 100.0 1/3 -2/3 -4 -2.5d 0d]\n");
+        let mut interpreter = crate::Interpreter::new();
+        let before = interpreter.eval(source).unwrap();
+        let after = interpreter.eval(&formatted).unwrap();
+        assert_eq!(format!("{:?}", before), format!("{:?}", after));
+    }
+}
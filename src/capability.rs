@@ -0,0 +1,181 @@
+use std::fmt;
+
+// The kinds of effect a builtin may have. An interpreter only installs the
+// builtins whose capability it has been granted; the others are replaced by
+// functions that fail with a capability error, so untrusted programs can be run
+// safely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    // Builtins without effects (arithmetic, if, def, ...). These are always
+    // granted, since the prelude and nearly every program need them.
+    Pure,
+    // print and input
+    Console,
+    // exit
+    Exit,
+    // readFile, writeFile and fileExists
+    Filesystem,
+    // env
+    Environment,
+    // now
+    Clock,
+    // random
+    Random,
+    // exec
+    Process,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 8] = [
+        Capability::Pure,
+        Capability::Console,
+        Capability::Exit,
+        Capability::Filesystem,
+        Capability::Environment,
+        Capability::Clock,
+        Capability::Random,
+        Capability::Process,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Pure => "pure",
+            Capability::Console => "console",
+            Capability::Exit => "exit",
+            Capability::Filesystem => "filesystem",
+            Capability::Environment => "environment",
+            Capability::Clock => "clock",
+            Capability::Random => "random",
+            Capability::Process => "process",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Capability> {
+        Capability::ALL.iter().copied().find(|c| c.name() == name)
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// A set of capabilities. The default grants only what the builtins always had:
+// pure, console and exit. Hosts opt in to the others, as the command line does
+// with all().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    bits: u8,
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Capability::ALL
+            .iter()
+            .fold(Capabilities::pure(), |caps, &c| caps.with(c))
+    }
+
+    // Only the pure builtins.
+    pub fn pure() -> Self {
+        Capabilities {
+            bits: Capability::Pure.bit(),
+        }
+    }
+
+    pub fn with(self, capability: Capability) -> Self {
+        Capabilities {
+            bits: self.bits | capability.bit(),
+        }
+    }
+
+    pub fn without(self, capability: Capability) -> Self {
+        if capability == Capability::Pure {
+            return self;
+        }
+        Capabilities {
+            bits: self.bits & !capability.bit(),
+        }
+    }
+
+    pub fn allows(self, capability: Capability) -> bool {
+        self.bits & capability.bit() != 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Capability> {
+        Capability::ALL
+            .iter()
+            .copied()
+            .filter(move |&c| self.allows(c))
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities::pure()
+            .with(Capability::Console)
+            .with(Capability::Exit)
+    }
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn denies_builtins_without_capability() {
+        let capabilities = Capabilities::pure()
+            .with(Capability::Clock)
+            .without(Capability::Pure);
+        assert!(capabilities.allows(Capability::Pure));
+        assert_eq!(
+            capabilities.iter().collect::<Vec<_>>(),
+            vec![Capability::Pure, Capability::Clock]
+        );
+
+        let mut interpreter = Interpreter::with_capabilities(capabilities);
+        assert_eq!(
+            *interpreter.eval("(> (now) 0)").unwrap(),
+            Value::Boolean(true)
+        );
+        let err = interpreter.eval("(print (+ 1 2))").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::Capability {
+                capability: "console".to_string()
+            }
+        );
+        let err = interpreter.eval("(exit 1)").unwrap_err();
+        assert_eq!(err.exit_code(), None);
+    }
+
+    #[test]
+    fn grants_only_console_and_exit_by_default() {
+        let mut interpreter = Interpreter::new();
+        let denied = [
+            ("(exec 'ls' [])", "process"),
+            ("(writeFile 'x' '')", "filesystem"),
+        ];
+        for (program, capability) in denied {
+            let err = interpreter.eval(program).unwrap_err();
+            assert!(err.to_string().contains("capability not granted"));
+            assert_eq!(
+                err.kind,
+                ErrorKind::Capability {
+                    capability: capability.to_string()
+                }
+            );
+        }
+        let err = interpreter.eval("(exit 3)").unwrap_err();
+        assert_eq!(err.exit_code(), Some(3));
+    }
+}
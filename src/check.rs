@@ -0,0 +1,150 @@
+use crate::capability::Capabilities;
+use crate::common::*;
+use crate::expressions::*;
+use crate::output::Output;
+use crate::stdlib;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+// Static checks over a parsed program. Oak is dynamic, so these only report
+// things that are certain to fail: currently, calls to functions that are never
+// defined anywhere.
+pub fn check(expressions: &[Rc<dyn Expression>]) -> Vec<EvalError> {
+    let mut globals: HashSet<String> = HashSet::new();
+
+    let mut builtins = Scope::new(None);
+    stdlib::insert_stdlib(&mut builtins, &Output::default(), Capabilities::all());
+    globals.extend(builtins.names());
+    globals.insert("args".to_string());
+
+    // def can be called anywhere, so any name def'd in the program counts.
+    for expr in stdlib::get_prelude().iter().chain(expressions) {
+        collect_defs(&**expr, &mut globals);
+    }
+
+    let mut checker = Checker {
+        globals,
+        parameters: Vec::new(),
+        errors: Vec::new(),
+    };
+    for expr in expressions {
+        checker.visit(&**expr);
+    }
+    checker.errors
+}
+
+// If this is a `(def .'name' ...)` call with a literal name, returns the name.
+pub fn def_name(call: &FunctionExpression) -> Option<&str> {
+    match call.function.node() {
+        Node::Identifier(id) if id.name == "def" => (),
+        _ => return None,
+    }
+    let name_fn = match call.arguments.first().map(|arg| arg.node()) {
+        Some(Node::Literal(literal)) => match &*literal.value {
+            Value::Function(fn_obj) => fn_obj,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match name_fn.body.node() {
+        Node::Literal(literal) => match &*literal.value {
+            Value::StringType(name) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Calls f on each direct sub-expression of expr.
+pub fn for_each_child(expr: &dyn Expression, mut f: impl FnMut(&dyn Expression)) {
+    match expr.node() {
+        Node::Literal(literal) => {
+            if let Value::Function(fn_obj) = &*literal.value {
+                f(&*fn_obj.body);
+            }
+        }
+        Node::Block(block) => block.expressions.iter().for_each(|e| f(&**e)),
+        Node::Call(call) => {
+            f(&*call.function);
+            call.arguments.iter().for_each(|e| f(&**e));
+        }
+        Node::Array(array) => array.elements.iter().for_each(|e| f(&**e)),
+        Node::Map(map) => map.entries.iter().for_each(|(key, value)| {
+            f(&**key);
+            f(&**value);
+        }),
+        Node::Record(record) => record.fields.iter().for_each(|(_, value)| f(&**value)),
+        Node::Match(matcher) => {
+            f(&*matcher.subject);
+            for arm in &matcher.arms {
+                arm.guard.iter().for_each(|guard| f(&**guard));
+                f(&*arm.body);
+            }
+        }
+        Node::Identifier(_) | Node::Native => (),
+    }
+}
+
+fn collect_defs(expr: &dyn Expression, names: &mut HashSet<String>) {
+    if let Node::Call(call) = expr.node() {
+        if let Some(name) = def_name(call) {
+            names.insert(name.to_string());
+        }
+    }
+    for_each_child(expr, |child| collect_defs(child, names));
+}
+
+struct Checker {
+    globals: HashSet<String>,
+    // parameter names of the enclosing function literals, innermost last
+    parameters: Vec<Vec<String>>,
+    errors: Vec<EvalError>,
+}
+
+impl Checker {
+    fn is_bound(&self, name: &str) -> bool {
+        name == "^"
+            || self.globals.contains(name)
+            || self
+                .parameters
+                .iter()
+                .any(|params| params.iter().any(|p| p == name))
+    }
+
+    fn visit(&mut self, expr: &dyn Expression) {
+        match expr.node() {
+            Node::Call(call) => {
+                if let Node::Identifier(id) = call.function.node() {
+                    if !self.is_bound(&id.name) {
+                        let mut error = EvalError::unbound(&id.name);
+                        if let Some(location) = call.location {
+                            error = error.at(location);
+                        }
+                        self.errors.push(error);
+                    }
+                }
+            }
+            Node::Literal(literal) => {
+                if let Value::Function(fn_obj) = &*literal.value {
+                    self.parameters.push(fn_obj.parameters.clone());
+                    self.visit(&*fn_obj.body);
+                    self.parameters.pop();
+                    return;
+                }
+            }
+            // names bound by a pattern are in scope in the arm's guard and body
+            Node::Match(matcher) => {
+                self.visit(&*matcher.subject);
+                for arm in &matcher.arms {
+                    self.parameters.push(arm.pattern.names());
+                    arm.guard.iter().for_each(|guard| self.visit(&**guard));
+                    self.visit(&*arm.body);
+                    self.parameters.pop();
+                }
+                return;
+            }
+            _ => (),
+        }
+        for_each_child(expr, |child| self.visit(child));
+    }
+}
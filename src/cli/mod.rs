@@ -0,0 +1,144 @@
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::process;
+
+use oak::EvalError;
+
+pub mod repl;
+pub mod run;
+pub mod test;
+pub mod tools;
+
+// Exit statuses, following the BSD sysexits conventions.
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_DATAERR: i32 = 65;
+pub const EXIT_NOINPUT: i32 = 66;
+pub const EXIT_SOFTWARE: i32 = 70;
+pub const EXIT_NOPERM: i32 = 77;
+
+pub const USAGE: &str = "usage: oak [<command>] [options] [args...]
+
+commands:
+  run [--result labeled|plain|quoted|json|none] [-q] [--allow <capabilities>]
+      <program> [args...]
+                     run a program (the default when no command is given);
+                     --allow limits its builtins to the given comma-separated
+                     capabilities (console, exit, filesystem, environment,
+                     clock, random, process)
+  check [--types] <program>
+                     parse the program and run the static checks; --types
+                     also infers types and reports conflicts between them
+  fmt [--write] <program>
+                     print the program in the canonical style
+  ast [--json] <program>
+                     print the parsed program as an indented tree or JSON
+  test [paths...]    run the .oak test files in the given files and directories
+                     (default: tests)
+  repl               start an interactive session
+
+<program> is a file name, - to read from stdin, or -e <expression>.";
+
+pub fn usage_error(message: &str) -> ! {
+    eprintln!("oak: {}", message);
+    eprintln!("{}", USAGE);
+    process::exit(EXIT_USAGE);
+}
+
+// Where the program text comes from.
+pub enum Source {
+    File(String),
+    Stdin,
+    Expression(String),
+}
+
+impl Source {
+    // Parses a program argument (a file name, `-` or `-e <expression>`) from the
+    // front of args, returning None if args is empty.
+    pub fn from_args<'a, I>(args: &mut I) -> Option<Source>
+    where
+        I: Iterator<Item = &'a String>,
+    {
+        let arg = args.next()?;
+        Some(match arg.as_str() {
+            "-e" => match args.next() {
+                Some(expression) => Source::Expression(expression.clone()),
+                None => usage_error("-e requires an expression"),
+            },
+            "-" => Source::Stdin,
+            "--" => Source::File(args.next()?.clone()),
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option {}", arg)),
+            _ => Source::File(arg.clone()),
+        })
+    }
+
+    // Name used for the program in diagnostics.
+    pub fn name(&self) -> &str {
+        match self {
+            Source::File(filename) => filename,
+            Source::Stdin => "<stdin>",
+            Source::Expression(_) => "<expression>",
+        }
+    }
+
+    pub fn read(&self) -> String {
+        let result = match self {
+            Source::File(filename) => fs::read_to_string(filename),
+            Source::Stdin => {
+                let mut program = String::new();
+                io::stdin().read_to_string(&mut program).map(|_| program)
+            }
+            Source::Expression(expression) => return expression.clone(),
+        };
+
+        result.unwrap_or_else(|err| {
+            eprintln!("oak: {}: {}", self.name(), describe_io_error(&err));
+            process::exit(io_error_status(&err));
+        })
+    }
+}
+
+pub fn describe_io_error(err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::NotFound => "file not found".to_string(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        _ => err.to_string(),
+    }
+}
+
+pub fn io_error_status(err: &io::Error) -> i32 {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => EXIT_NOPERM,
+        _ => EXIT_NOINPUT,
+    }
+}
+
+// Writes an error to stderr, with its location and the offending source line
+// when they are known. Every command reports errors this way.
+pub fn report_error(name: &str, program: &str, error: &EvalError) {
+    match error.location {
+        Some(location) => {
+            eprintln!("{}:{}: {}: {}", name, location, error.kind, error.reason);
+            if let Some(line) = program.lines().nth(location.line - 1) {
+                let gutter = location.line.to_string();
+                eprintln!("{} | {}", gutter, line);
+                eprintln!(
+                    "{} | {}^",
+                    " ".repeat(gutter.len()),
+                    " ".repeat(location.column - 1)
+                );
+            }
+        }
+        None => eprintln!("{}: {}: {}", name, error.kind, error.reason),
+    }
+}
+
+// Exit status for a program that failed with the given error.
+pub fn error_status(error: &EvalError) -> i32 {
+    match error.kind {
+        oak::ErrorKind::Exit(code) => code,
+        oak::ErrorKind::Syntax => EXIT_DATAERR,
+        oak::ErrorKind::Capability { .. } => EXIT_NOPERM,
+        _ => EXIT_SOFTWARE,
+    }
+}
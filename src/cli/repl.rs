@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::Ordering;
+
+use oak::{Capabilities, Interpreter};
+
+use super::*;
+
+const HELP: &str = "Enter Oak expressions to evaluate them. The value of the last input is
+available as ^. Input with unclosed brackets or strings continues on the next line.
+Ctrl-C interrupts the evaluation in progress.
+
+meta-commands:
+  :type <expression>  evaluate the expression and show its value's type
+                      (without changing ^)
+  :env                list the names defined in this session
+  :load <file>        evaluate a file in this session
+  :reset              start over with a fresh session
+  :history            show the inputs entered so far
+  :help               show this message
+  :quit               leave the REPL (as does end of input)";
+
+// Whether the input ends inside a string or with unclosed brackets, in which
+// case the REPL reads another line before evaluating it.
+fn is_incomplete(input: &str) -> bool {
+    let mut open_brackets = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for ch in input.chars() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == q {
+                quote = None;
+            }
+            continue;
+        }
+
+        match ch {
+            '\'' | '"' => quote = Some(ch),
+            '(' | '{' | '[' => open_brackets.push(ch),
+            // An unmatched closing bracket is a syntax error, which evaluating the
+            // input will report.
+            ')' | '}' | ']' if open_brackets.pop().is_none() => return false,
+            _ => (),
+        }
+    }
+
+    quote.is_some() || !open_brackets.is_empty()
+}
+
+// Where inputs are saved: $OAK_HISTORY, or ~/.oak_history if it isn't set.
+// Setting OAK_HISTORY to nothing turns saving off.
+fn history_path() -> Option<PathBuf> {
+    choose_history_path(env::var_os("OAK_HISTORY"), env::var_os("HOME"))
+}
+
+fn choose_history_path(oak_history: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    match oak_history {
+        Some(path) if path.is_empty() => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => home.map(|home| PathBuf::from(home).join(".oak_history")),
+    }
+}
+
+struct Repl {
+    interpreter: Interpreter,
+    // names defined by a fresh session, which :env leaves out
+    builtins: HashSet<String>,
+    history: Vec<String>,
+    // the handler making Ctrl-C interrupt the interpreter
+    #[cfg(unix)]
+    ctrl_c: Option<signal_hook::SigId>,
+}
+
+impl Repl {
+    fn new() -> Self {
+        let interpreter = Interpreter::with_capabilities(Capabilities::all());
+        let builtins = interpreter.names().into_iter().collect();
+
+        let history = history_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        let mut repl = Repl {
+            interpreter,
+            builtins,
+            history,
+            #[cfg(unix)]
+            ctrl_c: None,
+        };
+        repl.interrupt_on_ctrl_c();
+        repl
+    }
+
+    // Makes Ctrl-C set the interpreter's interrupt flag, rather than quit.
+    #[cfg(unix)]
+    fn interrupt_on_ctrl_c(&mut self) {
+        if let Some(handler) = self.ctrl_c.take() {
+            signal_hook::low_level::unregister(handler);
+        }
+        let flag = self.interpreter.interrupt_flag();
+        self.ctrl_c = signal_hook::flag::register(signal_hook::consts::SIGINT, flag).ok();
+    }
+
+    #[cfg(not(unix))]
+    fn interrupt_on_ctrl_c(&mut self) {}
+
+    fn add_history(&mut self, input: &str) {
+        // Multi-line inputs are stored on a single line. Failing to save them
+        // (to a read-only home, say) isn't worth interrupting the session for.
+        let entry = input.lines().collect::<Vec<_>>().join(" ");
+        if let Some(path) = history_path() {
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                writeln!(file, "{}", entry).ok();
+            }
+        }
+        self.history.push(entry);
+    }
+
+    // Evaluates Oak source in the session, printing the value or the error.
+    fn eval(&mut self, name: &str, input: &str) {
+        // forget any Ctrl-C pressed while the input was typed
+        self.interpreter
+            .interrupt_flag()
+            .store(false, Ordering::Relaxed);
+        match self.interpreter.eval(input) {
+            Ok(val) => println!("{}", val),
+            Err(e) => match e.exit_code() {
+                Some(code) => process::exit(code),
+                None => report_error(name, input, &e),
+            },
+        }
+    }
+
+    fn meta_command(&mut self, input: &str) {
+        let (command, argument) = match input.find(char::is_whitespace) {
+            Some(index) => (&input[..index], input[index..].trim()),
+            None => (input, ""),
+        };
+
+        match command {
+            // the expression runs (with its side effects), but ^ is kept
+            ":type" => match self.interpreter.eval_aside(argument) {
+                Ok(val) => println!("{}", val.type_name()),
+                Err(e) => report_error("<repl>", argument, &e),
+            },
+            ":env" => {
+                for name in self.interpreter.names() {
+                    if !self.builtins.contains(&name) {
+                        println!("{}", name);
+                    }
+                }
+            }
+            ":load" => match fs::read_to_string(argument) {
+                Ok(program) => self.eval(argument, &program),
+                Err(err) => eprintln!("oak: {}: {}", argument, describe_io_error(&err)),
+            },
+            ":reset" => {
+                self.interpreter = Interpreter::with_capabilities(Capabilities::all());
+                self.interrupt_on_ctrl_c();
+            }
+            ":history" => {
+                for (number, entry) in self.history.iter().enumerate() {
+                    println!("{:>4}  {}", number + 1, entry);
+                }
+            }
+            ":help" => println!("{}", HELP),
+            ":quit" => process::exit(0),
+            _ => eprintln!("unknown command {}; try :help", command),
+        }
+    }
+}
+
+pub fn main(args: &[String]) {
+    if let Some(arg) = args.first() {
+        usage_error(&format!("unexpected argument {}", arg));
+    }
+
+    let mut repl = Repl::new();
+    let stdin = io::stdin();
+    let mut input = String::new();
+
+    loop {
+        print!("{}", if input.is_empty() { "oak> " } else { "...> " });
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => {
+                println!();
+                break;
+            }
+            Ok(_) => (),
+            Err(err) => {
+                eprintln!("oak: {}", err);
+                break;
+            }
+        }
+        input.push_str(&line);
+
+        if input.trim().is_empty() {
+            input.clear();
+            continue;
+        }
+        if is_incomplete(&input) {
+            continue;
+        }
+
+        let entry = input.trim().to_string();
+        input.clear();
+        repl.add_history(&entry);
+
+        if entry.starts_with(':') {
+            repl.meta_command(&entry);
+        } else {
+            repl.eval("<repl>", &entry);
+        }
+    }
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn detects_incomplete_input() {
+        assert!(super::is_incomplete("(def .'f' /x .{"));
+        assert!(super::is_incomplete("(print 'it\\'s"));
+        assert!(super::is_incomplete("[1 2 (f"));
+        assert!(!super::is_incomplete("(print '({[')"));
+        assert!(!super::is_incomplete("(f))"));
+    }
+
+    #[test]
+    fn chooses_history_path() {
+        use super::choose_history_path;
+        use std::path::PathBuf;
+        let home = Some("/home/oak".into());
+        assert_eq!(
+            choose_history_path(None, home.clone()),
+            Some(PathBuf::from("/home/oak/.oak_history"))
+        );
+        assert_eq!(
+            choose_history_path(Some("/tmp/history".into()), home.clone()),
+            Some(PathBuf::from("/tmp/history"))
+        );
+        assert_eq!(choose_history_path(Some("".into()), home), None);
+        assert_eq!(choose_history_path(None, None), None);
+    }
+}
@@ -0,0 +1,107 @@
+use std::process;
+
+use oak::{Capabilities, Capability, Config};
+
+use super::*;
+
+// How the value of the program is printed once it finishes.
+#[derive(Clone, Copy, PartialEq)]
+enum ResultFormat {
+    Labeled,
+    Plain,
+    Quoted,
+    Json,
+    None,
+}
+
+impl ResultFormat {
+    fn parse(name: &str) -> Option<ResultFormat> {
+        match name {
+            "labeled" => Some(ResultFormat::Labeled),
+            "plain" => Some(ResultFormat::Plain),
+            "quoted" => Some(ResultFormat::Quoted),
+            "json" => Some(ResultFormat::Json),
+            "none" => Some(ResultFormat::None),
+            _ => None,
+        }
+    }
+}
+
+// Parses a comma-separated list of capability names. The pure builtins are
+// always allowed.
+fn parse_capabilities(names: &str) -> Capabilities {
+    names
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .fold(
+            Capabilities::pure(),
+            |caps, name| match Capability::from_name(name) {
+                Some(capability) => caps.with(capability),
+                None => usage_error(&format!("unknown capability {}", name)),
+            },
+        )
+}
+
+pub fn main(args: &[String]) {
+    let mut result_format = ResultFormat::Labeled;
+    let mut capabilities = Capabilities::all();
+
+    // Options are only recognized up to the program; everything after it belongs
+    // to the program.
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.peek() {
+        let format_name = if *arg == "--result" {
+            args.next();
+            match args.next() {
+                Some(name) => Some(name.as_str()),
+                None => usage_error("--result requires a format"),
+            }
+        } else if let Some(name) = arg.strip_prefix("--result=") {
+            args.next();
+            Some(name)
+        } else if *arg == "-q" || *arg == "--quiet" {
+            args.next();
+            Some("none")
+        } else if *arg == "--allow" {
+            args.next();
+            match args.next() {
+                Some(names) => capabilities = parse_capabilities(names),
+                None => usage_error("--allow requires a list of capabilities"),
+            }
+            None
+        } else if let Some(names) = arg.strip_prefix("--allow=") {
+            args.next();
+            capabilities = parse_capabilities(names);
+            None
+        } else {
+            break;
+        };
+
+        if let Some(name) = format_name {
+            result_format = ResultFormat::parse(name)
+                .unwrap_or_else(|| usage_error(&format!("unknown result format {}", name)));
+        }
+    }
+
+    let source = Source::from_args(&mut args).unwrap_or_else(|| usage_error("missing filename!"));
+    let program = source.read();
+    let prgm_config = Config::new(program)
+        .with_args(args.cloned().collect())
+        .with_capabilities(capabilities);
+
+    match prgm_config.run() {
+        Ok(val) => match result_format {
+            ResultFormat::Labeled => println!("Oak - result: {}", &*val),
+            ResultFormat::Plain => println!("{}", val.to_plain_string()),
+            ResultFormat::Quoted => println!("{}", &*val),
+            ResultFormat::Json => println!("{}", val.to_json()),
+            ResultFormat::None => (),
+        },
+        Err(e) => {
+            if e.exit_code().is_none() {
+                report_error(source.name(), &prgm_config.program, &e);
+            }
+            process::exit(error_status(&e));
+        }
+    };
+}
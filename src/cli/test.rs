@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use oak::{Capabilities, Config, Value};
+
+use super::*;
+
+// Collects the .oak files under path, in sorted order.
+fn collect_tests(path: &Path, tests: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        tests.push(path.to_path_buf());
+        return;
+    }
+
+    let entries = fs::read_dir(path).unwrap_or_else(|err| {
+        eprintln!("oak: {}: {}", path.display(), describe_io_error(&err));
+        process::exit(io_error_status(&err));
+    });
+    let mut entries: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "oak") {
+            collect_tests(&entry, tests);
+        }
+    }
+}
+
+// Runs a test file. A test passes if it runs to completion without evaluating to
+// false, or if it calls (exit 0).
+fn run_test(path: &Path) -> bool {
+    let name = path.display().to_string();
+    let program = match fs::read_to_string(path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("oak: {}: {}", name, describe_io_error(&err));
+            return false;
+        }
+    };
+
+    match Config::new(program.clone())
+        .with_capabilities(Capabilities::all())
+        .run()
+    {
+        Ok(val) => match &*val {
+            Value::Boolean(false) => {
+                eprintln!("{}: test evaluated to false", name);
+                false
+            }
+            _ => true,
+        },
+        Err(e) => match e.exit_code() {
+            Some(code) => code == 0,
+            None => {
+                report_error(&name, &program, &e);
+                false
+            }
+        },
+    }
+}
+
+pub fn main(args: &[String]) {
+    let paths: Vec<&str> = if args.is_empty() {
+        vec!["tests"]
+    } else {
+        args.iter().map(String::as_str).collect()
+    };
+
+    let mut tests = Vec::new();
+    for path in paths {
+        collect_tests(Path::new(path), &mut tests);
+    }
+
+    let mut failed = 0;
+    for test in &tests {
+        let passed = run_test(test);
+        println!(
+            "test {} ... {}",
+            test.display(),
+            if passed { "ok" } else { "FAILED" }
+        );
+        if !passed {
+            failed += 1;
+        }
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        tests.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::process;
+
+use oak::Program;
+
+use super::*;
+
+// Parses the program given on the command line, exiting with a diagnostic if it
+// has syntax errors.
+fn parse_source(source: &Source) -> (String, Program) {
+    let program = source.read();
+    match oak::parse(&program) {
+        Ok(parsed) => (program, parsed),
+        Err(e) => {
+            report_error(source.name(), &program, &e);
+            process::exit(error_status(&e));
+        }
+    }
+}
+
+fn single_source(args: &[String]) -> Source {
+    let mut args = args.iter();
+    let source = Source::from_args(&mut args).unwrap_or_else(|| usage_error("missing filename!"));
+    if let Some(arg) = args.next() {
+        usage_error(&format!("unexpected argument {}", arg));
+    }
+    source
+}
+
+pub fn check(args: &[String]) {
+    let types = args.first().is_some_and(|arg| arg == "--types");
+    let args = if types { &args[1..] } else { args };
+
+    let source = single_source(args);
+    let (program, parsed) = parse_source(&source);
+
+    let mut errors = parsed.check();
+    if types {
+        errors.extend(parsed.check_types());
+    }
+    for error in &errors {
+        report_error(source.name(), &program, error);
+    }
+    if !errors.is_empty() {
+        process::exit(EXIT_DATAERR);
+    }
+}
+
+pub fn fmt(args: &[String]) {
+    let write = args
+        .first()
+        .is_some_and(|arg| arg == "--write" || arg == "-w");
+    let args = if write { &args[1..] } else { args };
+
+    let source = single_source(args);
+    let (_, parsed) = parse_source(&source);
+    let formatted = parsed.to_source();
+
+    match &source {
+        Source::File(filename) if write => {
+            if let Err(err) = fs::write(filename, formatted) {
+                eprintln!("oak: {}: {}", filename, describe_io_error(&err));
+                process::exit(io_error_status(&err));
+            }
+        }
+        _ if write => usage_error("--write requires a file"),
+        _ => print!("{}", formatted),
+    }
+}
+
+pub fn ast(args: &[String]) {
+    let json = args.first().is_some_and(|arg| arg == "--json");
+    let args = if json { &args[1..] } else { args };
+
+    let source = single_source(args);
+    let (_, parsed) = parse_source(&source);
+
+    if json {
+        println!("{}", parsed.to_json());
+    } else {
+        print!("{}", parsed.to_tree());
+    }
+}
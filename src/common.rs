@@ -1,15 +1,31 @@
 use super::expressions::*;
+use crate::decimal::Decimal;
+use crate::host::argument_error;
+use crate::number::{self, Number};
+use crate::record::Record;
+use crate::variant::Variant;
+use num_bigint::{BigInt, Sign};
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::iter::Peekable;
 use std::ops::{Add, Mul};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub parameters: Vec<String>,
+    // The types the parameters are annotated with (`/x:number .`), if any. It is
+    // empty for functions without annotations.
+    pub parameter_types: Vec<Option<&'static str>>,
     pub body: Rc<dyn Expression>,
     pub closure: Option<Rc<Scope>>,
 }
@@ -26,12 +42,21 @@ impl Function {
         let closure_scope = match &self.closure {
             Some(s) => s,
             None => {
-                return Err(EvalError::new(
+                return Err(EvalError::host(
                     "Functions must have a closure scope!".to_string(),
                 ))
             }
         };
-        let closure_scope = Rc::clone(&closure_scope);
+        let closure_scope = Rc::clone(closure_scope);
+
+        if closure_scope.take_interrupt() {
+            return Err(EvalError::new(
+                ErrorKind::Interrupted,
+                "evaluation was interrupted!".to_string(),
+            ));
+        }
+
+        self.check_argument_types(&arguments)?;
 
         // Then, create a function scope with the values of the arguments
         let fn_scope = Scope::new(Some(closure_scope));
@@ -46,85 +71,582 @@ impl Function {
 
         // Then, evaluate the function body. Note that for now, pipe is given null
         // in a new function evaluation.
-        Ok(self
-            .body
-            .evaluate(Rc::new(fn_scope), Rc::new(Value::Null))?)
+        self.body.evaluate(Rc::new(fn_scope), Rc::new(Value::Null))
+    }
+}
+
+impl Function {
+    // The type the parameter at the position is annotated with, if any.
+    pub fn parameter_type(&self, position: usize) -> Option<&'static str> {
+        self.parameter_types.get(position).copied().flatten()
+    }
+
+    // Checks the arguments against the parameters' annotations. A missing
+    // argument counts as null.
+    fn check_argument_types(&self, arguments: &[Rc<Value>]) -> Result<(), EvalError> {
+        let null = Rc::new(Value::Null);
+        let annotated = self.parameters.iter().zip(&self.parameter_types);
+        for (position, (name, expected)) in annotated.enumerate() {
+            let actual = arguments.get(position).unwrap_or(&null);
+            match expected {
+                Some(expected) if !has_type(actual, expected) => {
+                    return Err(EvalError::type_error(
+                        expected,
+                        actual,
+                        Some(position),
+                        format!(
+                            "parameter {} must be a {}, not a {}!",
+                            name,
+                            expected,
+                            actual.type_name()
+                        ),
+                    ))
+                }
+                _ => (),
+            }
+        }
+        Ok(())
     }
 }
 
+// The names type_name returns, which (with `any`) are the types that parameters
+// and definitions can be annotated with.
+pub const TYPE_NAMES: [&str; 10] = [
+    "number", "string", "boolean", "function", "array", "map", "record", "variant", "null", "any",
+];
+
+// The type name as a static string, if it is one of TYPE_NAMES.
+pub fn type_name(name: &str) -> Option<&'static str> {
+    TYPE_NAMES
+        .iter()
+        .find(|type_name| **type_name == name)
+        .copied()
+}
+
+// Whether the value has the type named by an annotation.
+pub fn has_type(value: &Value, type_name: &str) -> bool {
+    type_name == "any" || value.type_name() == type_name
+}
+
+// Functions are equal only to themselves: the same function literal, evaluated
+// in the same scope. Builtins are each one function.
 impl PartialEq for Function {
-    fn eq(&self, _other: &Self) -> bool {
-        false
+    fn eq(&self, other: &Self) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Function {
+    // The addresses of the body and closure, which identify a function.
+    fn identity(&self) -> (*const (), *const ()) {
+        let closure = match &self.closure {
+            Some(closure) => Rc::as_ptr(closure) as *const (),
+            None => std::ptr::null(),
+        };
+        (Rc::as_ptr(&self.body) as *const (), closure)
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub enum Value {
+    // A float; the other kinds of numbers are exact (see number.rs)
     Number(f64),
+    Integer(BigInt),
+    Rational(BigRational),
+    Decimal(Decimal),
     StringType(String),
     Boolean(bool),
     Function(Function),
+    Array(Vec<Rc<Value>>),
+    Map(HashMap<Key, Rc<Value>>),
+    Record(Record),
+    Variant(Variant),
     Null,
 }
 
-impl Add for &Value {
-    type Output = Rc<Value>;
-
-    fn add(self, rhs: Self) -> Rc<Value> {
-        let output = match self {
-            Value::Number(num1) => {
-                if let Value::Number(num2) = rhs {
-                    Value::Number(num1 + num2)
-                } else if let Value::Null = rhs {
-                    Value::Number(*num1)
-                } else {
-                    Value::Null
-                }
+// A key of a map, which can be any value. Two keys are the same key when their
+// values are the same (see Value::same), so 1, 1.0 and 1.0d are one key, and NaN
+// can be looked up. Keys are ordered as their values are by Value::total_cmp.
+#[derive(Debug, Clone)]
+pub struct Key(Rc<Value>);
+
+impl Key {
+    pub fn new(value: Rc<Value>) -> Key {
+        Key(value)
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+
+    pub fn to_value(&self) -> Rc<Value> {
+        Rc::clone(&self.0)
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.same(&other.0)
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+// The entries of a map, sorted by key, for when their order is visible.
+pub fn sorted_entries(map: &HashMap<Key, Rc<Value>>) -> Vec<(&Key, &Rc<Value>)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+impl Value {
+    // Name of the value's type, as reported by the `type` builtin and in errors.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) | Value::Integer(_) | Value::Rational(_) | Value::Decimal(_) => {
+                "number"
             }
-            Value::StringType(str1) => {
-                if let Value::StringType(str2) = rhs {
-                    Value::StringType(str1.clone() + str2)
-                } else {
-                    Value::Null
-                }
+            Value::StringType(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Function(_) => "function",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Record(_) => "record",
+            Value::Variant(_) => "variant",
+            Value::Null => "null",
+        }
+    }
+
+    // Calls a function with the given arguments. Arrays can be called too, with
+    // an index, which returns the element at that index (or null if it is out of
+    // range), maps, with a key, records, with the name of a field, and variants,
+    // with the index of a payload value.
+    pub fn call(&self, arguments: Vec<Rc<Value>>) -> Result<Rc<Value>, EvalError> {
+        match self {
+            Value::Function(fn_obj) => fn_obj.call(arguments),
+            Value::Array(elements) => index_into(elements, "arrays", &arguments),
+            Value::Variant(variant) => index_into(variant.payload(), "variants", &arguments),
+            // Maps can be called with a key, returning its value (or null).
+            Value::Map(map) => match arguments.as_slice() {
+                [key] => Ok(map
+                    .get(&Key::new(Rc::clone(key)))
+                    .map(Rc::clone)
+                    .unwrap_or_else(|| Rc::new(Value::Null))),
+                _ => Err(EvalError::new(
+                    ErrorKind::Arity {
+                        expected: 1,
+                        actual: arguments.len(),
+                    },
+                    format!("maps take 1 argument, but were given {}!", arguments.len()),
+                )),
+            },
+            Value::Record(record) => match arguments.as_slice() {
+                [name] => match &**name {
+                    Value::StringType(name) => record.field_value(name),
+                    other => Err(EvalError::type_error(
+                        "string",
+                        other,
+                        Some(0),
+                        format!("record fields are named by strings, not {}!", other),
+                    )),
+                },
+                _ => Err(EvalError::new(
+                    ErrorKind::Arity {
+                        expected: 1,
+                        actual: arguments.len(),
+                    },
+                    format!(
+                        "records take 1 argument, but were given {}!",
+                        arguments.len()
+                    ),
+                )),
+            },
+            other => Err(EvalError::type_error(
+                "function",
+                other,
+                None,
+                "Only functions, arrays, maps, records and variants can be called!".to_string(),
+            )),
+        }
+    }
+
+    // Like Display, but strings are shown without quotes.
+    pub fn to_plain_string(&self) -> String {
+        match self {
+            Value::StringType(st) => st.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    // Renders the value as JSON. Values with no JSON equivalent (functions and
+    // non-finite numbers) become null.
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Number(num) if num.is_finite() => num.to_string(),
+            Value::Integer(int) => int.to_string(),
+            Value::Decimal(decimal) => decimal.to_string(),
+            // the nearest float, as JSON has no fractions
+            Value::Rational(rational) => {
+                Value::Number(rational.to_f64().unwrap_or(f64::NAN)).to_json()
             }
-            Value::Null => {
-                if let Value::Number(num2) = rhs {
-                    Value::Number(*num2)
-                } else {
-                    Value::Null
-                }
+            Value::StringType(st) => json_string(st),
+            Value::Boolean(b) => b.to_string(),
+            Value::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_json()).collect();
+                format!("[{}]", elements.join(","))
+            }
+            // Keys other than strings are converted to strings.
+            Value::Map(map) => {
+                let entries: Vec<String> = sorted_entries(map)
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = json_string(&key.to_value().to_plain_string());
+                        format!("{}:{}", key, value.to_json())
+                    })
+                    .collect();
+                format!("{{{}}}", entries.join(","))
+            }
+            Value::Record(record) => {
+                let fields: Vec<String> = record
+                    .fields()
+                    .map(|(name, value)| format!("{}:{}", json_string(name), value.to_json()))
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            }
+            Value::Variant(variant) => {
+                let payload: Vec<String> = variant.payload().iter().map(|v| v.to_json()).collect();
+                format!(
+                    "{{\"tag\":{},\"payload\":[{}]}}",
+                    json_string(variant.tag()),
+                    payload.join(",")
+                )
+            }
+            _ => "null".to_string(),
+        }
+    }
+}
+
+// The element at the index given as the only argument, or null if it is out of
+// range (or no index is given).
+fn index_into(
+    elements: &[Rc<Value>],
+    kind: &str,
+    arguments: &[Rc<Value>],
+) -> Result<Rc<Value>, EvalError> {
+    if arguments.len() > 1 {
+        return Err(EvalError::new(
+            ErrorKind::Arity {
+                expected: 1,
+                actual: arguments.len(),
+            },
+            format!(
+                "{} take 1 argument, but were given {}!",
+                kind,
+                arguments.len()
+            ),
+        ));
+    }
+    let index = match arguments.first().map(|index| &**index) {
+        None | Some(Value::Null) => return Ok(Rc::new(Value::Null)),
+        Some(index) => number::array_index(index)?,
+    };
+    match index.and_then(|index| elements.get(index)) {
+        Some(element) => Ok(Rc::clone(element)),
+        None => Ok(Rc::new(Value::Null)),
+    }
+}
+
+pub fn json_string(st: &str) -> String {
+    let mut out = String::with_capacity(st.len() + 2);
+    out.push('"');
+    for ch in st.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Numbers are added and strings joined, and null counts as 0 when added to a
+// number. Anything else is a type error.
+impl Add for &Value {
+    type Output = Result<Rc<Value>, EvalError>;
+
+    fn add(self, rhs: Self) -> Result<Rc<Value>, EvalError> {
+        let output = match (self, rhs) {
+            (Value::StringType(str1), Value::StringType(str2)) => {
+                Value::StringType(str1.clone() + str2)
             }
-            _ => Value::Null,
+            (Value::StringType(_), other) => return Err(argument_error("+", 1, "string", other)),
+            _ => match (Number::from_value(self), Number::from_value(rhs)) {
+                (Some(num1), Some(num2)) => num1.add(&num2).into_value(),
+                (Some(num), None) if matches!(rhs, Value::Null) => num.into_value(),
+                (None, Some(num)) if matches!(self, Value::Null) => num.into_value(),
+                (Some(_), None) => return Err(argument_error("+", 1, "number", rhs)),
+                (None, _) => return Err(argument_error("+", 0, "number", self)),
+            },
         };
-        Rc::new(output)
+        Ok(Rc::new(output))
     }
 }
 
+// Numbers are multiplied, and a string times a count is repeated. A number
+// times null is 0. Anything else is a type error.
 impl Mul for &Value {
-    type Output = Rc<Value>;
-
-    fn mul(self, rhs: Self) -> Rc<Value> {
-        let output = match self {
-            Value::Number(num1) => {
-                if let Value::Number(num2) = rhs {
-                    Value::Number(num1 * num2)
-                } else if let Value::Null = rhs {
-                    Value::Number(0.0)
-                } else {
-                    Value::Null
-                }
+    type Output = Result<Rc<Value>, EvalError>;
+
+    fn mul(self, rhs: Self) -> Result<Rc<Value>, EvalError> {
+        let output = match (self, rhs) {
+            (Value::StringType(str1), _) => Value::StringType(repeat(str1, rhs)?),
+            _ => match (Number::from_value(self), Number::from_value(rhs)) {
+                (Some(num1), Some(num2)) => num1.multiply(&num2)?.into_value(),
+                (Some(Number::Float(_)), None) if matches!(rhs, Value::Null) => Value::Number(0.0),
+                (Some(num), None) if matches!(rhs, Value::Null) => num
+                    .multiply(&Number::Integer(BigInt::from(0)))?
+                    .into_value(),
+                (Some(_), None) => return Err(argument_error("*", 1, "number", rhs)),
+                (None, _) => return Err(argument_error("*", 0, "number", self)),
+            },
+        };
+        Ok(Rc::new(output))
+    }
+}
+
+// Repeated strings can be at most this many bytes long.
+pub const MAX_STRING_LENGTH: usize = 1 << 30;
+
+// A string times a count: the count has to be a whole number that isn't
+// negative, and the result can't be longer than MAX_STRING_LENGTH.
+fn repeat(string: &str, times: &Value) -> Result<String, EvalError> {
+    let count = Number::from_value(times)
+        .and_then(|num| num.to_integer())
+        .filter(|count| count.sign() != Sign::Minus)
+        .ok_or_else(|| argument_error("*", 1, "non-negative integer", times))?;
+    if string.is_empty() {
+        return Ok(String::new());
+    }
+    match count.to_usize() {
+        Some(times) if times <= MAX_STRING_LENGTH / string.len() => Ok(string.repeat(times)),
+        _ => Err(EvalError::new(
+            ErrorKind::Limit,
+            format!(
+                "repeated strings can be at most {} bytes long, not {} times {}!",
+                MAX_STRING_LENGTH,
+                string.len(),
+                count
+            ),
+        )),
+    }
+}
+
+// `=`: numbers are equal if they have the same value, whatever their kind, and
+// NaN is equal to nothing. Arrays, maps, records and variants are equal if their
+// contents are (records whatever the order of their fields), and functions only
+// to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::StringType(a), Value::StringType(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Record(a), Value::Record(b)) => a == b,
+            (Value::Variant(a), Value::Variant(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            _ => match (Number::from_value(self), Number::from_value(other)) {
+                (Some(a), Some(b)) => a.compare(&b) == Some(Ordering::Equal),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Value {
+    // Deep equality under which every value is the same as itself, NaN included.
+    // Otherwise it agrees with `=`; it is what makes two map keys the same.
+    pub fn same(&self, other: &Value) -> bool {
+        self.total_cmp(other) == Ordering::Equal
+    }
+
+    // A total order over all values, consistent with `same`, which is what
+    // `compare` returns. Values of different types are ordered by type: null,
+    // booleans, numbers, strings, arrays, maps, records, variants and then
+    // functions. Within a type:
+    //  - numbers by value, with NaN after all other numbers;
+    //  - booleans with false first;
+    //  - strings by code point;
+    //  - arrays element by element, a prefix first;
+    //  - maps by their entries in key order, and records by their fields in
+    //    name order, in the same way;
+    //  - variants by tag and then payload;
+    //  - functions in an order that is fixed while they exist, but arbitrary.
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        self.total_by(other, str::cmp)
+    }
+
+    // Like total_cmp, but strings (including those in arrays, maps and so on) are
+    // compared with their case folded, so 'a' and 'A' are equal. Folding is the
+    // same in every locale.
+    pub fn compare_folded(&self, other: &Value) -> Ordering {
+        self.total_by(other, |a, b| fold_case(a).cmp(fold_case(b)))
+    }
+
+    // The order of `<`, `>`, `<=` and `>=`: total_cmp, except that NaN is not
+    // ordered with respect to anything, as with `=`. That holds inside arrays,
+    // maps, records and variants too, wherever the comparison gets to a NaN:
+    // `[nan]` and `[nan]` are unordered, but `[1 nan]` is less than `[2 nan]`.
+    // Map keys are compared with total_cmp, as they are by `=`.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        self.compare_by(other, str::cmp, true)
+    }
+
+    fn total_by(&self, other: &Value, strings: fn(&str, &str) -> Ordering) -> Ordering {
+        self.compare_by(other, strings, false)
+            .expect("only NaN is unordered")
+    }
+
+    fn compare_by(
+        &self,
+        other: &Value,
+        strings: fn(&str, &str) -> Ordering,
+        unordered_nan: bool,
+    ) -> Option<Ordering> {
+        let compare_all = |a: &[Rc<Value>], b: &[Rc<Value>]| {
+            let elements = a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| a.compare_by(b, strings, unordered_nan));
+            first_difference(elements).map(|ordering| ordering.then_with(|| a.len().cmp(&b.len())))
+        };
+        match (self, other) {
+            (Value::StringType(a), Value::StringType(b)) => Some(strings(a, b)),
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (Value::Function(a), Value::Function(b)) => Some(a.identity().cmp(&b.identity())),
+            (Value::Array(a), Value::Array(b)) => compare_all(a, b),
+            (Value::Map(a), Value::Map(b)) => {
+                let (a, b) = (sorted_entries(a), sorted_entries(b));
+                let entries = a
+                    .iter()
+                    .zip(&b)
+                    .map(|((key_a, value_a), (key_b, value_b))| {
+                        match key_a.value().total_by(key_b.value(), strings) {
+                            Ordering::Equal => value_a.compare_by(value_b, strings, unordered_nan),
+                            key => Some(key),
+                        }
+                    });
+                first_difference(entries)
+                    .map(|ordering| ordering.then_with(|| a.len().cmp(&b.len())))
             }
-            Value::StringType(str1) => {
-                if let Value::Number(num2) = rhs {
-                    Value::StringType(str1.repeat(*num2 as usize))
-                } else {
-                    Value::Null
-                }
+            (Value::Record(a), Value::Record(b)) => {
+                let (a, b) = (sorted_fields(a), sorted_fields(b));
+                let fields = a
+                    .iter()
+                    .zip(&b)
+                    .map(
+                        |((name_a, value_a), (name_b, value_b))| match name_a.cmp(name_b) {
+                            Ordering::Equal => value_a.compare_by(value_b, strings, unordered_nan),
+                            name => Some(name),
+                        },
+                    );
+                first_difference(fields)
+                    .map(|ordering| ordering.then_with(|| a.len().cmp(&b.len())))
             }
-            _ => Value::Null,
-        };
-        Rc::new(output)
+            (Value::Variant(a), Value::Variant(b)) => match a.tag().cmp(b.tag()) {
+                Ordering::Equal => compare_all(a.payload(), b.payload()),
+                tag => Some(tag),
+            },
+            (Value::Null, Value::Null) => Some(Ordering::Equal),
+            _ => match (Number::from_value(self), Number::from_value(other)) {
+                (Some(a), Some(b)) => match (a.is_nan(), b.is_nan()) {
+                    (false, false) => a.compare(&b),
+                    _ if unordered_nan => None,
+                    (a, b) => Some(a.cmp(&b)),
+                },
+                _ => Some(self.rank().cmp(&other.rank())),
+            },
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Boolean(_) => 1,
+            Value::Number(_) | Value::Integer(_) | Value::Rational(_) | Value::Decimal(_) => 2,
+            Value::StringType(_) => 3,
+            Value::Array(_) => 4,
+            Value::Map(_) => 5,
+            Value::Record(_) => 6,
+            Value::Variant(_) => 7,
+            Value::Function(_) => 8,
+        }
+    }
+}
+
+// The first ordering that isn't Equal, or None if a pair is unordered first.
+fn first_difference<I: Iterator<Item = Option<Ordering>>>(mut orderings: I) -> Option<Ordering> {
+    orderings
+        .find(|ordering| *ordering != Some(Ordering::Equal))
+        .unwrap_or(Some(Ordering::Equal))
+}
+
+fn fold_case(st: &str) -> impl Iterator<Item = char> + '_ {
+    st.chars().flat_map(char::to_lowercase)
+}
+
+fn sorted_fields(record: &Record) -> Vec<(&str, &Rc<Value>)> {
+    let mut fields: Vec<_> = record.fields().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    fields
+}
+
+// Consistent with `same`, so that any value can be a map key.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Value::StringType(st) => st.hash(state),
+            Value::Boolean(b) => b.hash(state),
+            Value::Function(function) => function.identity().hash(state),
+            Value::Array(elements) => elements.hash(state),
+            Value::Map(map) => sorted_entries(map).hash(state),
+            Value::Record(record) => sorted_fields(record).hash(state),
+            Value::Variant(variant) => {
+                variant.tag().hash(state);
+                variant.payload().hash(state);
+            }
+            Value::Null => (),
+            _ => match Number::from_value(self) {
+                Some(num) if num.is_nan() => f64::NAN.to_bits().hash(state),
+                Some(num) => num.hash(state),
+                None => (),
+            },
+        }
     }
 }
 
@@ -132,9 +654,25 @@ impl Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let val = match self {
             Value::Number(num) => num.to_string(),
+            Value::Integer(int) => int.to_string(),
+            Value::Rational(rational) => rational.to_string(),
+            Value::Decimal(decimal) => decimal.to_string(),
             Value::StringType(st) => format!("\"{}\"", st.clone()),
             Value::Boolean(b) => b.to_string(),
             Value::Function(_) => "Function".to_string(),
+            Value::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                format!("[{}]", elements.join(" "))
+            }
+            Value::Map(map) => {
+                let entries: Vec<String> = sorted_entries(map)
+                    .into_iter()
+                    .map(|(key, value)| format!("{} {}", key.to_value(), value))
+                    .collect();
+                format!("#{{{}}}", entries.join(" "))
+            }
+            Value::Record(record) => record.to_string(),
+            Value::Variant(variant) => variant.to_string(),
             Value::Null => "Null".to_string(),
         };
         write!(f, "{}", val)?;
@@ -145,6 +683,8 @@ impl Display for Value {
 pub struct Scope {
     map: RefCell<HashMap<String, Rc<Value>>>,
     parent: Option<Rc<Scope>>,
+    // set from outside to stop evaluation; only the global scope has one
+    interrupt: Option<Arc<AtomicBool>>,
 }
 
 impl Scope {
@@ -158,26 +698,71 @@ impl Scope {
             hash_map.insert(String::from("null"), Rc::new(Value::Null));
         }
 
+        let interrupt = match parent {
+            Some(_) => None,
+            None => Some(Arc::new(AtomicBool::new(false))),
+        };
         Scope {
             map: RefCell::new(hash_map),
             parent,
+            interrupt,
         }
     }
 
     pub fn get(&self, id: &str) -> Result<Rc<Value>, EvalError> {
+        // Undefined identifiers evaluate to null.
+        Ok(self.lookup(id).unwrap_or_else(|| Rc::new(Value::Null)))
+    }
+
+    // Like get, but distinguishes undefined identifiers from ones bound to null.
+    pub fn lookup(&self, id: &str) -> Option<Rc<Value>> {
         match self.map.borrow().get(id) {
-            Some(val) => Ok(Rc::clone(val)),
+            Some(val) => Some(Rc::clone(val)),
             None => match &self.parent {
-                Some(parent_scope) => parent_scope.get(id),
-                None => Ok(Rc::new(Value::Null)),
+                Some(parent_scope) => parent_scope.lookup(id),
+                None => None,
             },
         }
     }
 
+    // The interrupt flag of the global scope this scope belongs to. Setting it
+    // stops the evaluation in progress at its next function call.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        match (&self.interrupt, &self.parent) {
+            (Some(flag), _) => Arc::clone(flag),
+            (None, Some(parent)) => parent.interrupt_flag(),
+            (None, None) => unreachable!("a scope without a parent has a flag"),
+        }
+    }
+
+    // Whether evaluation was interrupted since the last check, which clears the
+    // flag, so that the next evaluation can go ahead.
+    fn take_interrupt(&self) -> bool {
+        match (&self.interrupt, &self.parent) {
+            (Some(flag), _) => {
+                flag.load(AtomicOrdering::Relaxed) && flag.swap(false, AtomicOrdering::Relaxed)
+            }
+            (None, Some(parent)) => parent.take_interrupt(),
+            (None, None) => false,
+        }
+    }
+
+    // Like lookup, but only in this scope, not its parents.
+    pub fn lookup_local(&self, id: &str) -> Option<Rc<Value>> {
+        self.map.borrow().get(id).map(Rc::clone)
+    }
+
     pub fn set(&self, id: String, val: Rc<Value>) {
         self.map.borrow_mut().insert(id, val);
     }
 
+    // Names defined directly in this scope (not its parents), in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.map.borrow().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
     pub fn display_map(&self) -> String {
         let mut string = String::new();
 
@@ -198,9 +783,24 @@ impl Debug for Scope {
     }
 }
 
+// A position in the program source. Lines and columns both start at 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 pub struct StringIterator<'a> {
     next_value: Option<char>,
-    iter: Box<dyn Iterator<Item = char> + 'a>,
+    iter: Peekable<Box<dyn Iterator<Item = char> + 'a>>,
+    // location of next_value
+    location: Location,
 }
 
 impl<'a> Iterator for StringIterator<'a> {
@@ -208,38 +808,206 @@ impl<'a> Iterator for StringIterator<'a> {
 
     fn next(&mut self) -> Option<char> {
         let val = self.next_value;
+        match val {
+            Some('\n') => {
+                self.location.line += 1;
+                self.location.column = 1;
+            }
+            Some(_) => self.location.column += 1,
+            None => (),
+        }
         self.next_value = self.iter.next();
         val
     }
 }
 
 impl<'a> StringIterator<'a> {
-    pub fn new(string: &str) -> StringIterator {
-        let mut iter = Box::new(string.chars());
+    pub fn new(string: &str) -> StringIterator<'_> {
+        let mut iter = (Box::new(string.chars()) as Box<dyn Iterator<Item = char>>).peekable();
         let next_value = iter.next();
 
-        StringIterator { next_value, iter }
+        StringIterator {
+            next_value,
+            iter,
+            location: Location { line: 1, column: 1 },
+        }
     }
 
     pub fn preview(&self) -> Option<char> {
         self.next_value
     }
+
+    // The character after the one returned by preview.
+    pub fn preview_second(&mut self) -> Option<char> {
+        self.iter.peek().copied()
+    }
+
+    // The location of the character returned by preview.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+}
+
+// The category of an EvalError, with any structured details that embedders may
+// want to inspect without parsing the human-readable reason.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    // The program text could not be parsed.
+    Syntax,
+    // A value of the wrong type was given. `position` is the zero-based index of
+    // the offending argument, when the error concerns a function argument.
+    Type {
+        expected: String,
+        actual: String,
+        position: Option<usize>,
+    },
+    // A function was called with the wrong number of arguments.
+    Arity {
+        expected: usize,
+        actual: usize,
+    },
+    // An identifier was used that is not defined in any enclosing scope.
+    Unbound {
+        name: String,
+    },
+    // A value would have been too large: a string over MAX_STRING_LENGTH, an
+    // exact number over the size or exponent limit, a decimal with more than
+    // MAX_SCALE places, or a variant with more than MAX_ARITY values.
+    Limit,
+    // Evaluation was stopped from outside the program.
+    Interrupted,
+    // The program called `exit` with the given code.
+    Exit(i32),
+    // A builtin was called that needs a capability the interpreter wasn't
+    // granted.
+    Capability {
+        capability: String,
+    },
+    // No arm of a `match` matched the value.
+    Match,
+    // Any other error, including errors raised by native (host) functions.
+    Host,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorKind::Syntax => "SyntaxError",
+            ErrorKind::Type { .. } => "TypeError",
+            ErrorKind::Arity { .. } => "ArityError",
+            ErrorKind::Unbound { .. } => "UnboundError",
+            ErrorKind::Limit => "LimitError",
+            ErrorKind::Interrupted => "Interrupted",
+            ErrorKind::Exit(_) => "Exit",
+            ErrorKind::Capability { .. } => "CapabilityError",
+            ErrorKind::Match => "MatchError",
+            ErrorKind::Host => "HostError",
+        };
+        f.write_str(name)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct EvalError {
+    pub kind: ErrorKind,
     pub reason: String,
+    pub location: Option<Location>,
 }
 
 impl EvalError {
-    pub fn new(reason: String) -> EvalError {
-        EvalError { reason }
+    pub fn new(kind: ErrorKind, reason: String) -> EvalError {
+        EvalError {
+            kind,
+            reason,
+            location: None,
+        }
+    }
+
+    // Attaches a location to the error, unless it already has a (more precise)
+    // one.
+    pub fn at(mut self, location: Location) -> EvalError {
+        if self.location.is_none() {
+            self.location = Some(location);
+        }
+        self
+    }
+
+    pub fn syntax(reason: String) -> EvalError {
+        EvalError::new(ErrorKind::Syntax, reason)
+    }
+
+    pub fn type_error(
+        expected: &str,
+        actual: &Value,
+        position: Option<usize>,
+        reason: String,
+    ) -> EvalError {
+        let kind = ErrorKind::Type {
+            expected: expected.to_string(),
+            actual: actual.type_name().to_string(),
+            position,
+        };
+        EvalError::new(kind, reason)
+    }
+
+    pub fn unbound(name: &str) -> EvalError {
+        EvalError::new(
+            ErrorKind::Unbound {
+                name: name.to_string(),
+            },
+            format!("{} is not defined!", name),
+        )
+    }
+
+    pub fn host(reason: String) -> EvalError {
+        EvalError::new(ErrorKind::Host, reason)
+    }
+
+    pub fn exit(code: i32) -> EvalError {
+        EvalError::new(ErrorKind::Exit(code), format!("exited with code {}", code))
+    }
+
+    pub fn capability(function: &str, capability: &str) -> EvalError {
+        EvalError::new(
+            ErrorKind::Capability {
+                capability: capability.to_string(),
+            },
+            format!(
+                "capability not granted: {} needs the {} capability!",
+                function, capability
+            ),
+        )
+    }
+
+    // The exit code, if this error is the program calling `exit` rather than a
+    // genuine failure.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.kind {
+            ErrorKind::Exit(code) => Some(code),
+            _ => None,
+        }
+    }
+}
+
+// Errors raised by host functions as strings.
+impl From<String> for EvalError {
+    fn from(reason: String) -> EvalError {
+        EvalError::host(reason)
+    }
+}
+
+impl From<&str> for EvalError {
+    fn from(reason: &str) -> EvalError {
+        EvalError::host(reason.to_string())
     }
 }
 
 impl Display for EvalError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "EvalError: {}", self.reason)?;
+        write!(f, "{}: {}", self.kind, self.reason)?;
+        if let Some(location) = self.location {
+            write!(f, " (at {})", location)?;
+        }
         Ok(())
     }
 }
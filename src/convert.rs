@@ -0,0 +1,372 @@
+use crate::common::*;
+use crate::decimal::Decimal;
+use crate::number::Number;
+use crate::record::Record;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+// Conversion of Oak values into Rust types. The lifetime allows borrowing from
+// the value, as `&str` does.
+pub trait FromOak<'a>: Sized {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError>;
+}
+
+// Conversion of Rust types into Oak values.
+pub trait IntoOak {
+    fn into_oak(self) -> Rc<Value>;
+}
+
+// The error for a value that cannot be converted to the expected Oak type.
+pub fn conversion_error(expected: &str, value: &Value) -> EvalError {
+    EvalError::type_error(
+        expected,
+        value,
+        None,
+        format!("expected a {}, got a {}!", expected, value.type_name()),
+    )
+}
+
+impl<'a> FromOak<'a> for Rc<Value> {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        Ok(Rc::clone(value))
+    }
+}
+
+impl<'a> FromOak<'a> for &'a Value {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        Ok(value)
+    }
+}
+
+// Integers convert to the nearest float.
+impl<'a> FromOak<'a> for f64 {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        Number::from_oak(value).map(|num| num.to_f64())
+    }
+}
+
+// Integers also convert from floats with no fractional part.
+impl<'a> FromOak<'a> for BigInt {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        Number::from_value(value)
+            .and_then(|num| num.to_integer())
+            .ok_or_else(|| conversion_error("integer", value))
+    }
+}
+
+// Any number but NaN and the infinities converts to its exact value.
+impl<'a> FromOak<'a> for BigRational {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        Number::from_value(value)
+            .and_then(|num| num.to_rational())
+            .ok_or_else(|| conversion_error("rational", value))
+    }
+}
+
+// Only integers and decimals convert to decimals, since they are exact.
+impl<'a> FromOak<'a> for Decimal {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        match &**value {
+            Value::Integer(int) => Ok(Decimal::from_integer(int.clone())),
+            Value::Decimal(decimal) => Ok(decimal.clone()),
+            other => Err(conversion_error("decimal", other)),
+        }
+    }
+}
+
+// Fixed-size integers convert from integers that are in range.
+macro_rules! from_oak_integer {
+    ($($ty:ty),*) => {$(
+        impl<'a> FromOak<'a> for $ty {
+            fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+                BigInt::from_oak(value)
+                    .ok()
+                    .and_then(|int| <$ty>::try_from(int).ok())
+                    .ok_or_else(|| conversion_error("integer", value))
+            }
+        }
+    )*};
+}
+
+from_oak_integer!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl<'a> FromOak<'a> for f32 {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        f64::from_oak(value).map(|num| num as f32)
+    }
+}
+
+impl<'a> FromOak<'a> for bool {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        match **value {
+            Value::Boolean(b) => Ok(b),
+            ref other => Err(conversion_error("boolean", other)),
+        }
+    }
+}
+
+impl<'a> FromOak<'a> for &'a str {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        match &**value {
+            Value::StringType(st) => Ok(st),
+            other => Err(conversion_error("string", other)),
+        }
+    }
+}
+
+impl<'a> FromOak<'a> for String {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        <&str>::from_oak(value).map(String::from)
+    }
+}
+
+impl<'a> FromOak<'a> for &'a Function {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        match &**value {
+            Value::Function(fn_obj) => Ok(fn_obj),
+            other => Err(conversion_error("function", other)),
+        }
+    }
+}
+
+impl<'a> FromOak<'a> for Function {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        <&Function>::from_oak(value).cloned()
+    }
+}
+
+// null converts to None, anything else as T.
+impl<'a, T: FromOak<'a>> FromOak<'a> for Option<T> {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        match **value {
+            Value::Null => Ok(None),
+            _ => T::from_oak(value).map(Some),
+        }
+    }
+}
+
+impl<'a> FromOak<'a> for &'a [Rc<Value>] {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        match &**value {
+            Value::Array(elements) => Ok(elements),
+            other => Err(conversion_error("array", other)),
+        }
+    }
+}
+
+impl<'a, T: FromOak<'a>> FromOak<'a> for Vec<T> {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        <&[Rc<Value>]>::from_oak(value)?
+            .iter()
+            .map(T::from_oak)
+            .collect()
+    }
+}
+
+// Tuples convert from arrays with exactly as many elements.
+macro_rules! from_oak_tuple {
+    ($len:expr; $($ty:ident $position:tt),*) => {
+        impl<'a, $($ty: FromOak<'a>),*> FromOak<'a> for ($($ty,)*) {
+            fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+                let values = <&[Rc<Value>]>::from_oak(value)?;
+                if values.len() != $len {
+                    return Err(EvalError::type_error(
+                        "array",
+                        value,
+                        None,
+                        format!("expected an array of {} elements, got {}!", $len, values.len()),
+                    ));
+                }
+                Ok(($($ty::from_oak(&values[$position])?,)*))
+            }
+        }
+    };
+}
+
+from_oak_tuple!(1; A 0);
+from_oak_tuple!(2; A 0, B 1);
+from_oak_tuple!(3; A 0, B 1, C 2);
+from_oak_tuple!(4; A 0, B 1, C 2, D 3);
+
+// Maps with string keys, and records, convert to maps.
+impl<'a, T: for<'b> FromOak<'b>> FromOak<'a> for HashMap<String, T> {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        let record = Record::from_oak(value).map_err(|_| conversion_error("map", value))?;
+        record
+            .names()
+            .map(|name| Ok((name.to_string(), record.get(name)?)))
+            .collect()
+    }
+}
+
+impl IntoOak for Rc<Value> {
+    fn into_oak(self) -> Rc<Value> {
+        self
+    }
+}
+
+impl IntoOak for Value {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(self)
+    }
+}
+
+impl IntoOak for f64 {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Number(self))
+    }
+}
+
+impl IntoOak for f32 {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Number(self as f64))
+    }
+}
+
+impl IntoOak for BigInt {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Integer(self))
+    }
+}
+
+// Whole rationals become integers.
+impl IntoOak for BigRational {
+    fn into_oak(self) -> Rc<Value> {
+        Number::rational(self).into_oak()
+    }
+}
+
+impl IntoOak for Decimal {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Decimal(self))
+    }
+}
+
+macro_rules! into_oak_integer {
+    ($($ty:ty),*) => {$(
+        impl IntoOak for $ty {
+            fn into_oak(self) -> Rc<Value> {
+                Rc::new(Value::Integer(BigInt::from(self)))
+            }
+        }
+    )*};
+}
+
+into_oak_integer!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl IntoOak for bool {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Boolean(self))
+    }
+}
+
+impl IntoOak for String {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::StringType(self))
+    }
+}
+
+impl IntoOak for &str {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::StringType(self.to_string()))
+    }
+}
+
+impl IntoOak for Function {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Function(self))
+    }
+}
+
+impl IntoOak for () {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Null)
+    }
+}
+
+// None converts to null.
+impl<T: IntoOak> IntoOak for Option<T> {
+    fn into_oak(self) -> Rc<Value> {
+        match self {
+            Some(val) => val.into_oak(),
+            None => Rc::new(Value::Null),
+        }
+    }
+}
+
+impl<T: IntoOak> IntoOak for Vec<T> {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Array(
+            self.into_iter().map(IntoOak::into_oak).collect(),
+        ))
+    }
+}
+
+impl<T: IntoOak> IntoOak for HashMap<String, T> {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Map(
+            self.into_iter()
+                .map(|(name, value)| (Key::new(name.into_oak()), value.into_oak()))
+                .collect(),
+        ))
+    }
+}
+
+macro_rules! into_oak_tuple {
+    ($($ty:ident $position:tt),*) => {
+        impl<$($ty: IntoOak),*> IntoOak for ($($ty,)*) {
+            fn into_oak(self) -> Rc<Value> {
+                vec![$(self.$position.into_oak()),*].into_oak()
+            }
+        }
+    };
+}
+
+into_oak_tuple!(A 0);
+into_oak_tuple!(A 0, B 1);
+into_oak_tuple!(A 0, B 1, C 2);
+into_oak_tuple!(A 0, B 1, C 2, D 3);
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn converts_structured_values() {
+        let mut interpreter = Interpreter::new();
+
+        let pairs: Vec<(String, i64)> =
+            Vec::from_oak(&interpreter.eval("[['a' 1] ['b' 2]]").unwrap()).unwrap();
+        assert_eq!(pairs, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+        assert!(i32::from_oak(&interpreter.eval("1.5").unwrap()).is_err());
+        assert!(<(f64, f64)>::from_oak(&interpreter.eval("[1]").unwrap()).is_err());
+
+        let mut scores = HashMap::new();
+        scores.insert("oak".to_string(), vec![1.0, 2.0]);
+        interpreter.set("scores", scores.clone());
+        assert_eq!(
+            *interpreter.eval("((scores 'oak') 1)").unwrap(),
+            Value::Number(2.0)
+        );
+        let back: HashMap<String, Vec<f64>> =
+            HashMap::from_oak(&interpreter.get("scores").unwrap()).unwrap();
+        assert_eq!(back, scores);
+
+        let point = Record::new().field("x", 1).field("y", "up").into_oak();
+        let record = Record::from_oak(&point).unwrap();
+        assert_eq!(record.names().collect::<Vec<_>>(), vec!["x", "y"]);
+        assert_eq!(record.get::<u8>("x").unwrap(), 1);
+        assert_eq!(record.get::<Option<f64>>("z").unwrap(), None);
+        assert_eq!(
+            record.get::<f64>("y").unwrap_err().to_string(),
+            "TypeError: field y must be a number, not a string!"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+// Fixed-point decimal numbers: an integer scaled by a power of ten, so 12.50 is
+// 1250 with a scale of 2. Unlike floats they hold decimal fractions exactly,
+// and they keep their number of places (12.50 is shown as 12.50, not 12.5).
+
+use crate::common::{ErrorKind, EvalError};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+// Quotients that don't terminate are rounded to this many places (or to the
+// places of the operands, if they have more).
+pub const DIVISION_PLACES: u32 = 20;
+
+// Decimals have at most this many places, since a larger scale would only
+// exhaust memory (like MAX_EXPONENT in number.rs).
+pub const MAX_SCALE: u32 = 1 << 16;
+
+// The scale, if it is within MAX_SCALE.
+pub fn check_scale(scale: u64) -> Result<u32, EvalError> {
+    match u32::try_from(scale) {
+        Ok(scale) if scale <= MAX_SCALE => Ok(scale),
+        _ => Err(EvalError::new(
+            ErrorKind::Limit,
+            format!(
+                "decimals can have at most {} places, not {}!",
+                MAX_SCALE, scale
+            ),
+        )),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decimal {
+    digits: BigInt,
+    scale: u32,
+}
+
+fn power_of_ten(exponent: u32) -> BigInt {
+    BigInt::from(10).pow(exponent)
+}
+
+impl Decimal {
+    pub fn new(digits: BigInt, scale: u32) -> Self {
+        Decimal { digits, scale }
+    }
+
+    pub fn from_integer(int: BigInt) -> Self {
+        Decimal::new(int, 0)
+    }
+
+    // Rounds a rational to the given number of places, with ties going to the
+    // even neighbour.
+    pub fn from_rational(rational: &BigRational, scale: u32) -> Self {
+        let scaled = rational * BigRational::from_integer(power_of_ten(scale));
+        let floor = scaled.floor();
+        let half = BigRational::new(BigInt::one(), BigInt::from(2));
+        let round_up = match (&scaled - &floor).cmp(&half) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => floor.to_integer().bit(0),
+        };
+        let digits = floor.to_integer() + if round_up { 1 } else { 0 };
+        Decimal::new(digits, scale)
+    }
+
+    // The rational exactly, if its decimal expansion terminates within
+    // MAX_SCALE places.
+    pub fn exact(rational: &BigRational) -> Option<Self> {
+        let mut denominator = rational.denom().clone();
+        let (mut twos, mut fives) = (0, 0);
+        while (&denominator % 2u32).is_zero() {
+            denominator /= 2u32;
+            twos += 1;
+        }
+        while (&denominator % 5u32).is_zero() {
+            denominator /= 5u32;
+            fives += 1;
+        }
+        if denominator.is_one() && twos.max(fives) <= MAX_SCALE {
+            Some(Decimal::from_rational(rational, twos.max(fives)))
+        } else {
+            None
+        }
+    }
+
+    // The float's shortest decimal representation, so 0.1 is 0.1 rather than
+    // the binary fraction the float actually holds.
+    pub fn from_f64(num: f64) -> Option<Self> {
+        if num.is_finite() {
+            num.to_string().parse().ok()
+        } else {
+            None
+        }
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    // The size of the digits, as the integer they form.
+    pub fn bits(&self) -> u64 {
+        self.digits.bits()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_zero()
+    }
+
+    pub fn to_rational(&self) -> BigRational {
+        BigRational::new(self.digits.clone(), power_of_ten(self.scale))
+    }
+
+    // The value, if it has no fractional part.
+    pub fn to_integer(&self) -> Option<BigInt> {
+        let rational = self.to_rational();
+        if rational.is_integer() {
+            Some(rational.to_integer())
+        } else {
+            None
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    pub fn round(&self, scale: u32) -> Result<Decimal, EvalError> {
+        let scale = check_scale(scale.into())?;
+        Ok(Decimal::from_rational(&self.to_rational(), scale))
+    }
+
+    // The digits at a larger scale.
+    fn digits_at(&self, scale: u32) -> BigInt {
+        &self.digits * power_of_ten(scale - self.scale)
+    }
+
+    pub fn add(&self, other: &Decimal) -> Decimal {
+        let scale = self.scale.max(other.scale);
+        Decimal::new(self.digits_at(scale) + other.digits_at(scale), scale)
+    }
+
+    pub fn multiply(&self, other: &Decimal) -> Result<Decimal, EvalError> {
+        let scale = check_scale(u64::from(self.scale) + u64::from(other.scale))?;
+        Ok(Decimal::new(&self.digits * &other.digits, scale))
+    }
+
+    pub fn negate(&self) -> Decimal {
+        Decimal::new(-&self.digits, self.scale)
+    }
+
+    // The quotient keeps at least the places of the operands, and is exact if it
+    // terminates; None if other is zero.
+    pub fn divide(&self, other: &Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let places = self.scale.max(other.scale);
+        let quotient = self.to_rational() / other.to_rational();
+        Some(match Decimal::exact(&quotient) {
+            Some(exact) if exact.scale <= places.max(DIVISION_PLACES) => {
+                Decimal::from_rational(&quotient, exact.scale.max(places))
+            }
+            _ => Decimal::from_rational(&quotient, places.max(DIVISION_PLACES)),
+        })
+    }
+
+    pub fn pow(&self, exponent: u32) -> Result<Decimal, EvalError> {
+        let scale = check_scale(u64::from(self.scale) * u64::from(exponent))?;
+        Ok(Decimal::new(self.digits.pow(exponent), scale))
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ();
+
+    // Parses an optional sign, digits and optionally a point and more digits.
+    fn from_str(st: &str) -> Result<Self, ()> {
+        let unsigned = st.strip_prefix('-').unwrap_or(st);
+        let (whole, fraction) = match unsigned.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (unsigned, ""),
+        };
+        let is_digits = |part: &str| part.chars().all(|ch| ch.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(());
+        }
+        let digits: BigInt = format!("{}{}", whole, fraction).parse().map_err(|_| ())?;
+        let scale = fraction
+            .len()
+            .to_u32()
+            .filter(|scale| *scale <= MAX_SCALE)
+            .ok_or(())?;
+        let digits = if st.starts_with('-') { -digits } else { digits };
+        Ok(Decimal::new(digits, scale))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.digits.is_negative() { "-" } else { "" };
+        let digits = self.digits.abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        // pad with zeros so there is at least one digit before the point
+        let padding = "0".repeat((scale + 1).saturating_sub(digits.len()));
+        let digits = padding + &digits;
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_and_divides() {
+        let price: Decimal = "19.99".parse().unwrap();
+        let total = price
+            .multiply(&Decimal::from_integer(BigInt::from(3)))
+            .unwrap();
+        assert_eq!(total.to_string(), "59.97");
+        assert_eq!("-0.05".parse::<Decimal>().unwrap().to_string(), "-0.05");
+        assert!("1.2.3".parse::<Decimal>().is_err());
+
+        let third = total.divide(&Decimal::from_integer(BigInt::from(3)));
+        assert_eq!(third.unwrap().to_string(), "19.99");
+        let third = Decimal::from_integer(BigInt::one()).divide(&"3".parse().unwrap());
+        assert_eq!(third.unwrap().to_string(), "0.33333333333333333333");
+        assert_eq!(
+            "2.5"
+                .parse::<Decimal>()
+                .unwrap()
+                .round(0)
+                .unwrap()
+                .to_string(),
+            "2"
+        );
+        assert_eq!(
+            "3.5"
+                .parse::<Decimal>()
+                .unwrap()
+                .round(0)
+                .unwrap()
+                .to_string(),
+            "4"
+        );
+        assert_eq!(Decimal::from_f64(0.1).unwrap().to_string(), "0.1");
+
+        // scales are limited, rather than exhausting memory
+        let err = "1.5".parse::<Decimal>().unwrap().pow(100_000).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Limit);
+        assert!(Decimal::from_integer(BigInt::one())
+            .round(u32::MAX)
+            .is_err());
+        assert!("1.5".parse::<Decimal>().unwrap().pow(u32::MAX).is_err());
+        let small = Decimal::new(BigInt::one(), MAX_SCALE);
+        assert!(small.to_string().starts_with("0.000"));
+    }
+}
@@ -1,4 +1,6 @@
 use super::common::*;
+use crate::record::Record;
+use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Debug;
 use std::rc::Rc;
@@ -10,6 +12,24 @@ mod parsers;
 // ################################################################
 pub trait Expression: fmt::Debug {
     fn evaluate(&self, scope: Rc<Scope>, pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError>;
+
+    // A view of the concrete expression, for tools that walk the parsed tree.
+    // Expressions implemented in Rust (such as builtins) are opaque.
+    fn node(&self) -> Node<'_> {
+        Node::Native
+    }
+}
+
+pub enum Node<'a> {
+    Literal(&'a LiteralExpression),
+    Identifier(&'a IdentifierExpression),
+    Block(&'a BlockExpression),
+    Call(&'a FunctionExpression),
+    Array(&'a ArrayExpression),
+    Map(&'a MapExpression),
+    Record(&'a RecordExpression),
+    Match(&'a MatchExpression),
+    Native,
 }
 
 // ################################################################
@@ -36,25 +56,21 @@ impl LiteralExpression {
                 value: parsers::function_parser(iter)?,
                 closure: true,
             }),
-            Some('[') => Ok(LiteralExpression {
-                value: parsers::array_parser(iter)?,
-                closure: true,
-            }),
-            Some(ch) => Err(EvalError::new(format!("Unknown character {}!", ch))),
-            None => Err(EvalError::new("End of string reached".to_string())),
+            Some(ch) => Err(EvalError::syntax(format!("Unknown character {}!", ch))),
+            None => Err(EvalError::syntax("End of string reached".to_string())),
         }
     }
 }
 
 impl Expression for LiteralExpression {
     fn evaluate(&self, scope: Rc<Scope>, _pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
-        // NOTE: Array and function literals require closure access. In other words,
+        // NOTE: Function literals require closure access. In other words,
         // the scope that gets passed in HERE is what they get evaluated in terms of.
         if self.closure {
             let fn_obj = if let Value::Function(obj) = &*self.value {
                 obj
             } else {
-                return Err(EvalError::new(
+                return Err(EvalError::host(
                     "Only functions may require closure access!".to_string(),
                 ));
             };
@@ -68,6 +84,10 @@ impl Expression for LiteralExpression {
             Ok(Rc::clone(&self.value))
         }
     }
+
+    fn node(&self) -> Node<'_> {
+        Node::Literal(self)
+    }
 }
 
 // ################################################################
@@ -102,6 +122,10 @@ impl Expression for IdentifierExpression {
             Ok(scope.get(&self.name)?)
         }
     }
+
+    fn node(&self) -> Node<'_> {
+        Node::Identifier(self)
+    }
 }
 
 // ################################################################
@@ -141,6 +165,21 @@ impl BlockExpression {
 
         Ok(BlockExpression { expressions })
     }
+
+    // Parses a whole program: every expression up to the end of the string.
+    pub fn program(iter: &mut StringIterator) -> Result<BlockExpression, EvalError> {
+        let mut expressions: Vec<Rc<dyn Expression>> = Vec::new();
+
+        while let Some(next_char) = iter.preview() {
+            if next_char.is_whitespace() {
+                iter.next();
+            } else {
+                expressions.push(parsers::generic(iter)?);
+            }
+        }
+
+        Ok(BlockExpression { expressions })
+    }
 }
 
 impl Expression for BlockExpression {
@@ -156,6 +195,10 @@ impl Expression for BlockExpression {
         // Loop through expressions, return result of the last one.
         Ok(val)
     }
+
+    fn node(&self) -> Node<'_> {
+        Node::Block(self)
+    }
 }
 
 // ################################################################
@@ -165,26 +208,40 @@ impl Expression for BlockExpression {
 pub struct FunctionExpression {
     pub function: Rc<dyn Expression>,
     pub arguments: Vec<Rc<dyn Expression>>,
+    // Where the expression starts in the source, if it was parsed from one
+    pub location: Option<Location>,
 }
 
 impl FunctionExpression {
     pub fn new(iter: &mut StringIterator) -> Result<FunctionExpression, EvalError> {
+        let location = iter.location();
+
         // Consume opening parenthesis
         iter.next();
 
         // First is the function itself.
         let function = parsers::generic(iter)?;
 
-        // Then arguments:
+        FunctionExpression::with_arguments(iter, function, location)
+    }
+
+    // Parses the arguments that follow the function, up to the closing
+    // parenthesis.
+    fn with_arguments(
+        iter: &mut StringIterator,
+        function: Rc<dyn Expression>,
+        location: Location,
+    ) -> Result<FunctionExpression, EvalError> {
         let mut arguments: Vec<Rc<dyn Expression>> = Vec::new();
 
         loop {
             let next_char = match iter.preview() {
                 Some(val) => val,
                 None => {
-                    return Err(EvalError::new(
+                    return Err(EvalError::syntax(
                         "End of function expression not found!".to_string(),
-                    ))
+                    )
+                    .at(location))
                 }
             };
 
@@ -203,42 +260,305 @@ impl FunctionExpression {
         Ok(FunctionExpression {
             function,
             arguments,
+            location: Some(location),
         })
     }
 }
 
 impl Expression for FunctionExpression {
     fn evaluate(&self, scope: Rc<Scope>, pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
+        let result = self.evaluate_call(scope, pipe_val);
+
+        // Errors are reported at the innermost call they occurred in
+        match self.location {
+            Some(location) => result.map_err(|e| e.at(location)),
+            None => result,
+        }
+    }
+
+    fn node(&self) -> Node<'_> {
+        Node::Call(self)
+    }
+}
+
+impl FunctionExpression {
+    fn evaluate_call(&self, scope: Rc<Scope>, pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
         // First, get the function object
         let fn_obj = self
             .function
             .evaluate(Rc::clone(&scope), Rc::clone(&pipe_val))?;
 
-        let fn_obj = match &*fn_obj {
-            Value::Function(obj) => obj,
-            _ => {
-                return Err(EvalError::new(
+        match &*fn_obj {
+            Value::Function(_)
+            | Value::Array(_)
+            | Value::Map(_)
+            | Value::Record(_)
+            | Value::Variant(_) => (),
+            other => {
+                if let Node::Identifier(id) = self.function.node() {
+                    if id.name != "^" && scope.lookup(&id.name).is_none() {
+                        return Err(EvalError::unbound(&id.name));
+                    }
+                }
+                return Err(EvalError::type_error(
+                    "function",
+                    other,
+                    None,
                     "the first item in a function expression does not evaluate to a function!"
                         .to_string(),
-                ))
+                ));
             }
         };
 
-        // Then, evaluate the arguments
-        let (args, errors): (Vec<_>, Vec<_>) = self
+        // Then, evaluate the arguments, stopping at the first error (or exit)
+        let args = self
             .arguments
             .iter()
             .map(|arg_expr| arg_expr.evaluate(Rc::clone(&scope), Rc::clone(&pipe_val)))
-            .partition(Result::is_ok);
+            .collect::<Result<Vec<_>, _>>()?;
 
-        if !errors.is_empty() {
-            return Err(errors[0].clone().unwrap_err());
+        // Finally, call the function
+        fn_obj.call(args)
+    }
+}
+
+// ################################################################
+// #                       ARRAY EXPRESSION                       #
+// ################################################################
+// An array literal, which evaluates each of its elements.
+#[derive(Debug)]
+pub struct ArrayExpression {
+    pub elements: Vec<Rc<dyn Expression>>,
+}
+
+impl Expression for ArrayExpression {
+    fn evaluate(&self, scope: Rc<Scope>, pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
+        let elements = self
+            .elements
+            .iter()
+            .map(|expr| expr.evaluate(Rc::clone(&scope), Rc::clone(&pipe_val)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Rc::new(Value::Array(elements)))
+    }
+
+    fn node(&self) -> Node<'_> {
+        Node::Array(self)
+    }
+}
+
+// ################################################################
+// #                        MAP EXPRESSION                        #
+// ################################################################
+// A map literal, `#{key value ...}`, which evaluates each key and value.
+#[derive(Debug)]
+pub struct MapExpression {
+    pub entries: Vec<(Rc<dyn Expression>, Rc<dyn Expression>)>,
+}
+
+impl Expression for MapExpression {
+    fn evaluate(&self, scope: Rc<Scope>, pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
+        let mut map = HashMap::new();
+        for (key, value) in &self.entries {
+            let key = key.evaluate(Rc::clone(&scope), Rc::clone(&pipe_val))?;
+            let value = value.evaluate(Rc::clone(&scope), Rc::clone(&pipe_val))?;
+            map.insert(Key::new(key), value);
         }
+        Ok(Rc::new(Value::Map(map)))
+    }
 
-        let args: Vec<Rc<Value>> = args.into_iter().map(Result::unwrap).collect();
+    fn node(&self) -> Node<'_> {
+        Node::Map(self)
+    }
+}
 
-        // Finally, call the function
-        fn_obj.call(args)
+// ################################################################
+// #                       RECORD EXPRESSION                      #
+// ################################################################
+// A record literal, `#(name value ...)`, which evaluates each value.
+#[derive(Debug)]
+pub struct RecordExpression {
+    pub fields: Vec<(String, Rc<dyn Expression>)>,
+}
+
+impl Expression for RecordExpression {
+    fn evaluate(&self, scope: Rc<Scope>, pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
+        let mut record = Record::new();
+        for (name, value) in &self.fields {
+            record.insert(
+                name,
+                value.evaluate(Rc::clone(&scope), Rc::clone(&pipe_val))?,
+            );
+        }
+        Ok(Rc::new(Value::Record(record)))
+    }
+
+    fn node(&self) -> Node<'_> {
+        Node::Record(self)
+    }
+}
+
+// ################################################################
+// #                       MATCH EXPRESSION                       #
+// ################################################################
+// `(match value pattern .body pattern when guard .body ...)`: evaluates the body
+// of the first arm whose pattern matches the value (and whose guard, if it has
+// one, is true), with the names bound by the pattern in scope.
+#[derive(Debug)]
+pub struct MatchExpression {
+    pub subject: Rc<dyn Expression>,
+    pub arms: Vec<MatchArm>,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Rc<dyn Expression>>,
+    pub body: Rc<dyn Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    // `_`, which matches anything
+    Wildcard,
+    // a name, which matches anything and binds it to the value
+    Bind(String),
+    // a number, string, boolean or null, which matches equal values
+    Literal(Rc<Value>),
+    // `(Tag p ...)`, or just `Tag` for variants without a payload
+    Variant(String, Vec<Pattern>),
+    // `[p ...]`, optionally ending with `...rest` to bind the remaining elements
+    Array(Vec<Pattern>, Option<String>),
+    // `#(name p ...)`, which matches records with at least the given fields
+    Record(Vec<(String, Pattern)>),
+}
+
+impl Pattern {
+    // Whether the value matches, adding the values of any names it binds.
+    pub fn matches(&self, value: &Rc<Value>, bindings: &mut Vec<(String, Rc<Value>)>) -> bool {
+        match (self, &**value) {
+            (Pattern::Wildcard, _) => true,
+            (Pattern::Bind(name), _) => {
+                bindings.push((name.clone(), Rc::clone(value)));
+                true
+            }
+            (Pattern::Literal(literal), value) => **literal == *value,
+            (Pattern::Variant(tag, patterns), Value::Variant(variant)) => {
+                variant.tag() == tag
+                    && variant.payload().len() == patterns.len()
+                    && Pattern::match_all(patterns, variant.payload(), bindings)
+            }
+            (Pattern::Array(patterns, rest), Value::Array(elements)) => {
+                let (first, others) = match rest {
+                    Some(_) if elements.len() >= patterns.len() => {
+                        elements.split_at(patterns.len())
+                    }
+                    None if elements.len() == patterns.len() => (elements.as_slice(), &[][..]),
+                    _ => return false,
+                };
+                if !Pattern::match_all(patterns, first, bindings) {
+                    return false;
+                }
+                if let Some(rest) = rest.as_ref().filter(|rest| *rest != "_") {
+                    bindings.push((rest.clone(), Rc::new(Value::Array(others.to_vec()))));
+                }
+                true
+            }
+            (Pattern::Record(fields), Value::Record(record)) => {
+                fields
+                    .iter()
+                    .all(|(name, pattern)| match record.value(name) {
+                        Some(field) => pattern.matches(field, bindings),
+                        None => false,
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    fn match_all(
+        patterns: &[Pattern],
+        values: &[Rc<Value>],
+        bindings: &mut Vec<(String, Rc<Value>)>,
+    ) -> bool {
+        patterns
+            .iter()
+            .zip(values)
+            .all(|(pattern, value)| pattern.matches(value, bindings))
+    }
+
+    // The names the pattern binds, in order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.collect_names(&mut names);
+        names
+    }
+
+    fn collect_names(&self, names: &mut Vec<String>) {
+        match self {
+            Pattern::Bind(name) => names.push(name.clone()),
+            Pattern::Variant(_, patterns) => patterns.iter().for_each(|p| p.collect_names(names)),
+            Pattern::Array(patterns, rest) => {
+                patterns.iter().for_each(|p| p.collect_names(names));
+                names.extend(rest.iter().filter(|rest| *rest != "_").cloned());
+            }
+            Pattern::Record(fields) => fields.iter().for_each(|(_, p)| p.collect_names(names)),
+            Pattern::Wildcard | Pattern::Literal(_) => (),
+        }
+    }
+}
+
+impl Expression for MatchExpression {
+    fn evaluate(&self, scope: Rc<Scope>, pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
+        let result = self.evaluate_arms(scope, pipe_val);
+
+        match self.location {
+            Some(location) => result.map_err(|e| e.at(location)),
+            None => result,
+        }
+    }
+
+    fn node(&self) -> Node<'_> {
+        Node::Match(self)
+    }
+}
+
+impl MatchExpression {
+    fn evaluate_arms(&self, scope: Rc<Scope>, pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
+        let subject = self
+            .subject
+            .evaluate(Rc::clone(&scope), Rc::clone(&pipe_val))?;
+
+        for arm in &self.arms {
+            let mut bindings = Vec::new();
+            if !arm.pattern.matches(&subject, &mut bindings) {
+                continue;
+            }
+            let arm_scope = Rc::new(Scope::new(Some(Rc::clone(&scope))));
+            for (name, value) in bindings {
+                arm_scope.set(name, value);
+            }
+            if let Some(guard) = &arm.guard {
+                match &*guard.evaluate(Rc::clone(&arm_scope), Rc::clone(&pipe_val))? {
+                    Value::Boolean(true) => (),
+                    Value::Boolean(false) => continue,
+                    other => {
+                        return Err(EvalError::type_error(
+                            "boolean",
+                            other,
+                            None,
+                            format!("match guards must be booleans, not {}!", other),
+                        ))
+                    }
+                }
+            }
+            return arm.body.evaluate(arm_scope, pipe_val);
+        }
+
+        Err(EvalError::new(
+            ErrorKind::Match,
+            format!("no arm of the match matches {}!", subject),
+        ))
     }
 }
 
@@ -247,6 +567,7 @@ impl Expression for FunctionExpression {
 // ################################################################
 #[cfg(test)]
 mod tests {
+    use super::Expression;
     use crate::common::*;
     use std::rc::Rc;
 
@@ -314,4 +635,212 @@ mod tests {
         let exp = super::BlockExpression::new(&mut s).unwrap();
         println!("{:#?}", exp);
     }
+
+    #[test]
+    fn reports_error_kinds() {
+        let s = &"(undefinedFn 1)".to_string();
+        let mut s = StringIterator::new(s);
+        let exp = super::FunctionExpression::new(&mut s).unwrap();
+        let scope = Rc::new(Scope::new(None));
+        let err = exp.evaluate(scope, Rc::new(Value::Null)).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::Unbound {
+                name: "undefinedFn".to_string()
+            }
+        );
+
+        let s = &"(test 'a'".to_string();
+        let mut s = StringIterator::new(s);
+        let err = super::FunctionExpression::new(&mut s).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Syntax);
+    }
+
+    #[test]
+    fn evaluates_arrays() {
+        let mut interpreter = crate::Interpreter::new();
+        let result = interpreter
+            .eval("(def .'arr' [1 null (+ 1 2)]) [(arr 2) (length arr) (arr 5)]")
+            .unwrap();
+        assert_eq!(result.to_string(), "[3 3 Null]");
+        let count = interpreter.eval("(foreach arr /item n .(+ n 1))").unwrap();
+        assert_eq!(*count, Value::Number(3.0));
+        let result = interpreter.eval("[(= [1 [2]] [1 [2]]) (type arr) (findIndex arr null)]");
+        assert_eq!(result.unwrap().to_string(), "[true \"array\" 1]");
+    }
+
+    #[test]
+    fn evaluates_maps() {
+        let mut interpreter = crate::Interpreter::new();
+        let result = interpreter
+            .eval("(def .'m' #{'b' 2 'a' (+ 1 0)}) [(m 'a') (get m 'c') (length m) (keys m)]")
+            .unwrap();
+        assert_eq!(result.to_string(), "[1 Null 2 [\"a\" \"b\"]]");
+        let result = interpreter
+            .eval("[(insert m 'c' 3) (remove m 'a') m]")
+            .unwrap();
+        assert_eq!(
+            result.to_string(),
+            "[#{\"a\" 1 \"b\" 2 \"c\" 3} #{\"b\" 2} #{\"a\" 1 \"b\" 2}]"
+        );
+        let result = interpreter
+            .eval("(#{[1 'a'] 2 1.0 'one'} [1 'a'])")
+            .unwrap();
+        assert_eq!(*result, Value::Number(2.0));
+    }
+
+    #[test]
+    fn evaluates_records() {
+        let mut interpreter = crate::Interpreter::new();
+        let result = interpreter
+            .eval("(def .'p' #(x 1 y (+ 1 1))) [(p 'y') (with p 'x' 3) p (fields p)]")
+            .unwrap();
+        assert_eq!(
+            result.to_string(),
+            "[2 #(x 3 y 2) #(x 1 y 2) [\"x\" \"y\"]]"
+        );
+        let result = interpreter.eval("[(= p #(y 2 x 1)) (= p #(x 1)) (type p)]");
+        assert_eq!(result.unwrap().to_string(), "[true false \"record\"]");
+        assert!(interpreter.eval("(p 'z')").is_err());
+        let err = interpreter.eval("#(x 1 x 2)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Syntax);
+    }
+
+    #[test]
+    fn compares_values() {
+        let mut interpreter = crate::Interpreter::new();
+        interpreter.set("nan", f64::NAN);
+        interpreter.eval("(def .'f' /x .x)").unwrap();
+        let result = interpreter
+            .eval("[(= f f) (= f /x .x) (= + +) (findIndex [1 f] f) (= #{'a' [1]} #{'a' [1]})]")
+            .unwrap();
+        assert_eq!(result.to_string(), "[true false true 1 true]");
+        let result = interpreter
+            .eval("[(= nan nan) (same nan nan) (same [1 nan] [1.0 nan]) (get #{nan 1} nan)]")
+            .unwrap();
+        assert_eq!(result.to_string(), "[false true true 1]");
+        let result = interpreter.eval("(keys #{'a' 1 [2] 2 null 3 1.5 4 true 5})");
+        assert_eq!(result.unwrap().to_string(), "[Null true 1.5 \"a\" [2]]");
+    }
+
+    #[test]
+    fn orders_values() {
+        let mut interpreter = crate::Interpreter::new();
+        interpreter.set("nan", f64::NAN);
+        let result = interpreter
+            .eval("[(< 'apple' 'banana') (< 'Z' 'a') (> [1 2] [1]) (<= false true) (< 1 'a')]")
+            .unwrap();
+        assert_eq!(result.to_string(), "[true true true true true]");
+        let result = interpreter
+            .eval(
+                "[(compare 'b' 'a') (compare [1 2] [1.0 2]) (compare null 0)
+                  (compareFolded 'Apple' 'aPPLE') (compareFolded 'Z' 'a') (< nan 1)]",
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "[1 0 -1 0 1 false]");
+        // containers holding values of different types compare them by type
+        let result = interpreter
+            .eval(
+                "[(< [1 'a'] [1 2]) (< [null] [false]) (< #{1 'a'} #{1 [1]}) (< #(a 1) #(a 'x'))
+                  (compare [1 'a'] ['a' 1]) (compare #{'a' 1} #{1 'a'}) (compare [[nan]] [['a']])]",
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "[false true true true -1 1 -1]");
+        let result = interpreter
+            .eval(
+                "(def .'Pair' (variant 'Pair' 2))
+                 [(< (Pair 1 nan) (Pair 2 nan)) (<= (Pair nan 1) (Pair nan 1))
+                  (< (Pair 'a' nan) (Pair [] nan)) (< [[nan]] [[nan 1]]) (< [nan 'a'] [1 'a'])]",
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "[true false true false false]");
+    }
+
+    #[test]
+    fn leaves_nested_nan_unordered() {
+        let mut interpreter = crate::Interpreter::new();
+        interpreter.set("nan", f64::NAN);
+        let result = interpreter
+            .eval(
+                "[(<= [nan] [nan]) (>= #{1 nan} #{1 nan}) (< #(a nan) #(a 1)) (= [nan] [nan])
+                  (< [1 nan] [2 nan]) (> #{'b' nan} #{'a' nan}) (compare [nan] [nan])]",
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "[false false false false true true 0]");
+    }
+
+    #[test]
+    fn evaluates_logical_operators() {
+        let mut interpreter = crate::Interpreter::new();
+        let result = interpreter
+            .eval("[(and true .false) (or false true) (not true) (xor true false) (xor true true)]")
+            .unwrap();
+        assert_eq!(result.to_string(), "[false true false true false]");
+        // the block isn't called, so there is no error
+        let result = interpreter
+            .eval("[(and false .(exit 1)) (or true .'not a boolean') (or false .(> 2 1))]")
+            .unwrap();
+        assert_eq!(result.to_string(), "[false true true]");
+        let err = interpreter.eval("(and true .null)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "TypeError: and takes booleans or blocks returning them, not a null! (at 1:1)"
+        );
+        assert!(interpreter.eval("(not 0)").is_err());
+    }
+
+    #[test]
+    fn matches_patterns() {
+        let mut interpreter = crate::Interpreter::new();
+        interpreter
+            .eval("(def .'Cons' (variant 'Cons' 2)) (def .'Nil' (variant 'Nil' 0))")
+            .unwrap();
+        let result = interpreter
+            .eval(
+                "(def .'sum' /list .(match list (Cons head tail) .(+ head (sum tail)) Nil .0))
+                [(sum (Cons 1 (Cons 2 Nil))) (Cons 1 Nil) (tag Nil)]",
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "[3 (Cons 1 Nil) \"Nil\"]");
+        let result = interpreter
+            .eval(
+                "[(match [1 2 3] [] .0 [x ...rest] .rest)
+                  (match #(x 1 y 2) #(x 0) .'origin' #(x x y y) when (> x y) .x _ .'other')
+                  (match 'b' 'a' .1 'b' .2)]",
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "[[2 3] \"other\" 2]");
+        let err = interpreter.eval("(match (Cons 1 Nil) Nil .0)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Match);
+        let err = interpreter.eval("(match 1 [x x] .x)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Syntax);
+        let err = interpreter.eval("(variant 'A' 100000000000)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Limit);
+    }
+
+    #[test]
+    fn checks_annotations() {
+        let mut interpreter = crate::Interpreter::new();
+        let result = interpreter
+            .eval("(def .'f' /x:number label .[x label]) (f 1 'one')")
+            .unwrap();
+        assert_eq!(result.to_string(), "[1 \"one\"]");
+        let err = interpreter.eval("(f 'one' 1)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "TypeError: parameter x must be a number, not a string! (at 1:1)"
+        );
+        assert_eq!(
+            err.kind,
+            ErrorKind::Type {
+                expected: "number".to_string(),
+                actual: "string".to_string(),
+                position: Some(0),
+            }
+        );
+        assert!(interpreter.eval("(def .'n' 1 'number')").is_ok());
+        assert!(interpreter.eval("(def .'n' null 'number')").is_err());
+        let err = interpreter.eval("/x:numbr .x").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Syntax);
+    }
 }
@@ -1,6 +1,6 @@
 use super::*;
 use crate::common::EvalError;
-use crate::stdlib::NativeExpression;
+use crate::number::Number;
 
 // Generic expression parser function, used whenever any expression has
 // sub expressions to evaluate: decides which expression is there, and calls
@@ -11,7 +11,7 @@ pub fn generic(iter: &mut StringIterator) -> Result<Rc<dyn Expression>, EvalErro
     let first_char = match iter.preview() {
         Some(val) => val,
         None => {
-            return Err(EvalError::new(
+            return Err(EvalError::syntax(
                 "End of string; nothing to parse".to_string(),
             ))
         }
@@ -20,21 +20,56 @@ pub fn generic(iter: &mut StringIterator) -> Result<Rc<dyn Expression>, EvalErro
     if first_char == '{' {
         Ok(Rc::new(BlockExpression::new(iter)?))
     } else if first_char == '(' {
-        Ok(Rc::new(FunctionExpression::new(iter)?))
+        call_parser(iter)
+    } else if first_char == '[' {
+        Ok(Rc::new(array_parser(iter)?))
+    } else if first_char == '#' && iter.preview_second() == Some('{') {
+        Ok(Rc::new(map_parser(iter)?))
+    } else if first_char == '#' && iter.preview_second() == Some('(') {
+        Ok(Rc::new(record_parser(iter)?))
     // Below are all characters that can begin a literal
-    } else if first_char.is_digit(10) || ['\'', '"', '[', '/', '.', '-'].contains(&first_char) {
+    } else if first_char.is_ascii_digit()
+        || ['\'', '"', '/', '.'].contains(&first_char)
+        || minus_begins_number(iter)
+    {
         Ok(Rc::new(LiteralExpression::new(iter)?))
     // Below are all reserved characters that are not covered by previous cases
     } else if !['}', ')', ']', '\\'].contains(&first_char) {
         Ok(Rc::new(IdentifierExpression::new(iter)?))
     } else {
-        Err(EvalError::new(format!(
+        Err(EvalError::syntax(format!(
             "Unknown expression type starting with character {}",
             first_char
         )))
     }
 }
 
+// A parenthesized expression: a function call, or a match if the function is
+// `match`.
+pub fn call_parser(iter: &mut StringIterator) -> Result<Rc<dyn Expression>, EvalError> {
+    let location = iter.location();
+
+    // consume opening parenthesis
+    iter.next();
+
+    let function = generic(iter)?;
+    match function.node() {
+        Node::Identifier(id) if id.name == "match" => Ok(Rc::new(match_parser(iter, location)?)),
+        _ => Ok(Rc::new(FunctionExpression::with_arguments(
+            iter, function, location,
+        )?)),
+    }
+}
+
+// Whether the next character is a minus sign beginning a number, as in `-1` or
+// `-.5`, rather than the `-` function.
+fn minus_begins_number(iter: &mut StringIterator) -> bool {
+    iter.preview() == Some('-')
+        && iter
+            .preview_second()
+            .is_some_and(|ch| ch.is_ascii_digit() || ch == '.')
+}
+
 pub fn number_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError> {
     let mut value = String::new();
 
@@ -44,24 +79,36 @@ pub fn number_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError>
             Some(val) => val,
             None => break,
         };
-        if !next_char.is_digit(10) && next_char != '.' {
+        if !next_char.is_ascii_digit() && next_char != '.' {
             break; // before the non-numeric character is consumed
         }
     }
 
-    let value: f64 = match value.parse() {
-        Ok(val) => val,
-        Err(_) => return Err(EvalError::new("Invalid numeric literal!".to_string())),
-    };
+    // a decimal's suffix, or the denominator of a rational
+    match iter.preview() {
+        Some('d') => value.extend(iter.next()),
+        Some('/') if iter.preview_second().is_some_and(|ch| ch.is_ascii_digit()) => {
+            value.extend(iter.next());
+            while iter.preview().is_some_and(|ch| ch.is_ascii_digit()) {
+                value.extend(iter.next());
+            }
+        }
+        _ => (),
+    }
 
-    Ok(Rc::new(Value::Number(value)))
+    match value.parse::<Number>() {
+        Ok(number) => Ok(Rc::new(number.into_value())),
+        Err(_) => Err(EvalError::syntax("Invalid numeric literal!".to_string())),
+    }
 }
 
 pub fn string_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError> {
+    let location = iter.location();
+
     // consume first char
     let first_char = match iter.next() {
         Some(val) => val,
-        None => return Err(EvalError::new("End of string reached".to_string())),
+        None => return Err(EvalError::syntax("End of string reached".to_string())),
     };
 
     let mut value = String::new();
@@ -71,9 +118,9 @@ pub fn string_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError>
         let this_char = match iter.next() {
             Some(val) => val,
             None => {
-                return Err(EvalError::new(
-                    "End of string literal not found!".to_string(),
-                ))
+                return Err(
+                    EvalError::syntax("End of string literal not found!".to_string()).at(location),
+                )
             }
         };
         if escaped {
@@ -126,7 +173,7 @@ pub fn function_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError
                     current_param.push(val);
                 }
                 None => {
-                    return Err(EvalError::new(
+                    return Err(EvalError::syntax(
                         "Reached end of string while parsing function parameters!".to_string(),
                     ))
                 }
@@ -134,6 +181,30 @@ pub fn function_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError
         }
     }
 
+    // Parameters may be annotated with a type, as in `x:number`.
+    let mut parameter_types = Vec::new();
+    for parameter in parameters.iter_mut() {
+        let annotation = match parameter.split_once(':') {
+            Some((name, annotation)) => match type_name(annotation) {
+                Some(annotation) => {
+                    *parameter = name.to_string();
+                    Some(annotation)
+                }
+                None => {
+                    return Err(EvalError::syntax(format!(
+                        "Unknown type {} for parameter {}!",
+                        annotation, name
+                    )))
+                }
+            },
+            None => None,
+        };
+        parameter_types.push(annotation);
+    }
+    if parameter_types.iter().all(Option::is_none) {
+        parameter_types.clear();
+    }
+
     // Since the '.' has been consumed, and we can only be here if we got it, we
     // can call generic right away.
 
@@ -141,6 +212,7 @@ pub fn function_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError
 
     let fn_obj = Function {
         parameters,
+        parameter_types,
         body,
         closure: None,
     };
@@ -148,7 +220,9 @@ pub fn function_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError
     Ok(Rc::new(Value::Function(fn_obj)))
 }
 
-pub fn array_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError> {
+pub fn array_parser(iter: &mut StringIterator) -> Result<ArrayExpression, EvalError> {
+    let location = iter.location();
+
     // consume opening bracket
     iter.next();
 
@@ -156,9 +230,8 @@ pub fn array_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError> {
 
     loop {
         match iter.preview() {
-            Some(' ') => {
+            Some(ch) if ch.is_whitespace() => {
                 iter.next();
-                continue;
             }
             Some(']') => {
                 iter.next();
@@ -167,34 +240,324 @@ pub fn array_parser(iter: &mut StringIterator) -> Result<Rc<Value>, EvalError> {
             Some(_) => {
                 expressions.push(generic(iter)?);
             }
-            None => return Err(EvalError::new("Unexpected end of file!".to_string())),
+            None => {
+                return Err(EvalError::syntax("Unexpected end of file!".to_string()).at(location))
+            }
         }
     }
 
-    let fn_body = NativeExpression::new(move |scope| {
-        let index = scope.get("index")?;
-        let index = if let Value::Number(i) = *index {
-            i as usize
-        } else if let Value::Null = *index {
-            return Ok(Rc::new(Value::Null));
-        } else {
-            return Err(EvalError::new(
-                "Arguments to arrays must be numbers!".to_string(),
-            ));
-        };
+    Ok(ArrayExpression {
+        elements: expressions,
+    })
+}
 
-        let expr = match expressions.get(index) {
-            Some(e) => e,
-            None => return Ok(Rc::new(Value::Null)),
-        };
-        Ok(expr.evaluate(scope, Rc::new(Value::Null))?)
-    });
+pub fn map_parser(iter: &mut StringIterator) -> Result<MapExpression, EvalError> {
+    let location = iter.location();
 
-    let fn_obj = Function {
-        parameters: vec!["index".to_string()],
-        body: Rc::new(fn_body),
-        closure: None,
+    // consume # and opening bracket
+    iter.next();
+    iter.next();
+
+    let mut expressions = Vec::new();
+
+    loop {
+        match iter.preview() {
+            Some(ch) if ch.is_whitespace() => {
+                iter.next();
+            }
+            Some('}') => {
+                iter.next();
+                break;
+            }
+            Some(_) => {
+                expressions.push(generic(iter)?);
+            }
+            None => {
+                return Err(EvalError::syntax("Unexpected end of file!".to_string()).at(location))
+            }
+        }
+    }
+
+    if expressions.len() % 2 != 0 {
+        return Err(
+            EvalError::syntax("Map literals need a value for every key!".to_string()).at(location),
+        );
+    }
+
+    let mut expressions = expressions.into_iter();
+    let mut entries = Vec::new();
+    while let (Some(key), Some(value)) = (expressions.next(), expressions.next()) {
+        entries.push((key, value));
+    }
+
+    Ok(MapExpression { entries })
+}
+
+pub fn record_parser(iter: &mut StringIterator) -> Result<RecordExpression, EvalError> {
+    let location = iter.location();
+
+    // consume # and opening parenthesis
+    iter.next();
+    iter.next();
+
+    let mut fields: Vec<(String, Rc<dyn Expression>)> = Vec::new();
+
+    loop {
+        match iter.preview() {
+            Some(ch) if ch.is_whitespace() => {
+                iter.next();
+            }
+            Some(')') => {
+                iter.next();
+                break;
+            }
+            Some(_) => {
+                let field_location = iter.location();
+                let name = IdentifierExpression::new(iter)?.name;
+                if name.is_empty() {
+                    return Err(EvalError::syntax(
+                        "Record fields must be named by identifiers!".to_string(),
+                    )
+                    .at(field_location));
+                }
+                if fields.iter().any(|(field, _)| *field == name) {
+                    return Err(EvalError::syntax(format!(
+                        "Record field {} is given twice!",
+                        name
+                    ))
+                    .at(field_location));
+                }
+                while iter.preview().is_some_and(char::is_whitespace) {
+                    iter.next();
+                }
+                match iter.preview() {
+                    Some(')') | None => {
+                        return Err(EvalError::syntax(format!(
+                            "Record field {} needs a value!",
+                            name
+                        ))
+                        .at(field_location))
+                    }
+                    Some(_) => fields.push((name, generic(iter)?)),
+                }
+            }
+            None => {
+                return Err(EvalError::syntax("Unexpected end of file!".to_string()).at(location))
+            }
+        }
+    }
+
+    Ok(RecordExpression { fields })
+}
+
+fn skip_whitespace(iter: &mut StringIterator) {
+    while iter.preview().is_some_and(char::is_whitespace) {
+        iter.next();
+    }
+}
+
+// Parses the rest of a match, after `(match`: the value, then arms of a pattern,
+// optionally `when` and a guard, and `.` and the body.
+fn match_parser(
+    iter: &mut StringIterator,
+    location: Location,
+) -> Result<MatchExpression, EvalError> {
+    skip_whitespace(iter);
+    let subject = match iter.preview() {
+        Some(')') | None => {
+            return Err(EvalError::syntax("match needs a value to match!".to_string()).at(location))
+        }
+        Some(_) => generic(iter)?,
     };
 
-    Ok(Rc::new(Value::Function(fn_obj)))
+    let mut arms = Vec::new();
+    loop {
+        skip_whitespace(iter);
+        match iter.preview() {
+            Some(')') => {
+                iter.next();
+                break;
+            }
+            Some(_) => arms.push(arm_parser(iter)?),
+            None => {
+                return Err(EvalError::syntax("End of match not found!".to_string()).at(location))
+            }
+        }
+    }
+
+    Ok(MatchExpression {
+        subject,
+        arms,
+        location: Some(location),
+    })
+}
+
+fn arm_parser(iter: &mut StringIterator) -> Result<MatchArm, EvalError> {
+    let location = iter.location();
+    let pattern = pattern_parser(iter)?;
+
+    let mut names = pattern.names();
+    names.sort();
+    if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(
+            EvalError::syntax(format!("{} is bound twice in a pattern!", pair[0])).at(location),
+        );
+    }
+
+    skip_whitespace(iter);
+    let guard = if iter.preview() == Some('.') {
+        None
+    } else {
+        let keyword = IdentifierExpression::new(iter)?.name;
+        if keyword != "when" {
+            return Err(EvalError::syntax(
+                "A match pattern must be followed by a body, or when and a guard!".to_string(),
+            )
+            .at(location));
+        }
+        skip_whitespace(iter);
+        Some(generic(iter)?)
+    };
+
+    skip_whitespace(iter);
+    if iter.next() != Some('.') {
+        return Err(
+            EvalError::syntax("A match arm needs a body, starting with .!".to_string())
+                .at(location),
+        );
+    }
+    let body = generic(iter)?;
+
+    Ok(MatchArm {
+        pattern,
+        guard,
+        body,
+    })
+}
+
+pub fn pattern_parser(iter: &mut StringIterator) -> Result<Pattern, EvalError> {
+    let location = iter.location();
+    let first_char = match iter.preview() {
+        Some(val) => val,
+        None => return Err(EvalError::syntax("Unexpected end of file!".to_string()).at(location)),
+    };
+
+    if first_char == '(' {
+        // consume opening parenthesis
+        iter.next();
+        skip_whitespace(iter);
+        let tag = IdentifierExpression::new(iter)?.name;
+        if tag.is_empty() {
+            return Err(
+                EvalError::syntax("Variant patterns must start with a tag!".to_string())
+                    .at(location),
+            );
+        }
+        let payload = patterns_until(iter, ')', location)?;
+        Ok(Pattern::Variant(tag, payload))
+    } else if first_char == '[' {
+        iter.next();
+        let mut elements = Vec::new();
+        loop {
+            skip_whitespace(iter);
+            match iter.preview() {
+                Some(']') => {
+                    iter.next();
+                    return Ok(Pattern::Array(elements, None));
+                }
+                Some('.') => break,
+                Some(_) => elements.push(pattern_parser(iter)?),
+                None => {
+                    return Err(
+                        EvalError::syntax("Unexpected end of file!".to_string()).at(location)
+                    )
+                }
+            }
+        }
+        // `...rest`, which must be last
+        let rest_location = iter.location();
+        let dots: String = (0..3).filter_map(|_| iter.next()).collect();
+        let rest = IdentifierExpression::new(iter)?.name;
+        skip_whitespace(iter);
+        if dots != "..." || rest.is_empty() || iter.next() != Some(']') {
+            return Err(
+                EvalError::syntax("Array patterns can only end with ...name!".to_string())
+                    .at(rest_location),
+            );
+        }
+        Ok(Pattern::Array(elements, Some(rest)))
+    } else if first_char == '#' && iter.preview_second() == Some('(') {
+        iter.next();
+        iter.next();
+        let mut fields: Vec<(String, Pattern)> = Vec::new();
+        loop {
+            skip_whitespace(iter);
+            match iter.preview() {
+                Some(')') => {
+                    iter.next();
+                    return Ok(Pattern::Record(fields));
+                }
+                Some(_) => {
+                    let field_location = iter.location();
+                    let name = IdentifierExpression::new(iter)?.name;
+                    skip_whitespace(iter);
+                    if name.is_empty() || matches!(iter.preview(), Some(')') | None) {
+                        return Err(EvalError::syntax(
+                            "Record patterns need a name and a pattern for every field!"
+                                .to_string(),
+                        )
+                        .at(field_location));
+                    }
+                    fields.push((name, pattern_parser(iter)?));
+                }
+                None => {
+                    return Err(
+                        EvalError::syntax("Unexpected end of file!".to_string()).at(location)
+                    )
+                }
+            }
+        }
+    } else if first_char.is_ascii_digit() || minus_begins_number(iter) {
+        Ok(Pattern::Literal(number_parser(iter)?))
+    } else if first_char == '\'' || first_char == '"' {
+        Ok(Pattern::Literal(string_parser(iter)?))
+    } else {
+        let name = IdentifierExpression::new(iter)?.name;
+        match name.as_str() {
+            "" => Err(EvalError::syntax(format!(
+                "Unknown pattern starting with character {}",
+                first_char
+            ))
+            .at(location)),
+            "_" => Ok(Pattern::Wildcard),
+            "true" => Ok(Pattern::Literal(Rc::new(Value::Boolean(true)))),
+            "false" => Ok(Pattern::Literal(Rc::new(Value::Boolean(false)))),
+            "null" => Ok(Pattern::Literal(Rc::new(Value::Null))),
+            // capitalized names are the tags of variants without a payload
+            _ if name.starts_with(char::is_uppercase) => Ok(Pattern::Variant(name, Vec::new())),
+            _ => Ok(Pattern::Bind(name)),
+        }
+    }
+}
+
+// Parses patterns up to and including the closing character.
+fn patterns_until(
+    iter: &mut StringIterator,
+    close: char,
+    location: Location,
+) -> Result<Vec<Pattern>, EvalError> {
+    let mut patterns = Vec::new();
+    loop {
+        skip_whitespace(iter);
+        match iter.preview() {
+            Some(ch) if ch == close => {
+                iter.next();
+                return Ok(patterns);
+            }
+            Some(_) => patterns.push(pattern_parser(iter)?),
+            None => {
+                return Err(EvalError::syntax("Unexpected end of file!".to_string()).at(location))
+            }
+        }
+    }
 }
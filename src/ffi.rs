@@ -0,0 +1,696 @@
+// The C API. include/oak.h is generated from this file by cbindgen (see
+// cbindgen.toml), and the `///` comments here are the documentation in it.
+//
+// No panic may unwind into C, so every entry point catches them: the functions
+// taking an interpreter report one as an error, and the others return their
+// fallback value (NULL, NaN, 0 or OAK_ERROR).
+
+use crate::capability::{Capabilities, Capability};
+use crate::common::*;
+use crate::host::HostFunction;
+use crate::interpreter::Interpreter;
+use crate::number::Number;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::any::Any;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::{c_char, c_double, c_int, c_longlong, c_uint};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::rc::Rc;
+
+/// Statuses returned by oak_eval, oak_call, oak_set and oak_register.
+pub const OAK_OK: c_int = 0;
+pub const OAK_ERROR: c_int = 1;
+/// The program called exit; see oak_exit_code.
+pub const OAK_EXIT: c_int = 2;
+
+/// Value types, as returned by oak_value_type.
+pub const OAK_NULL: c_int = 0;
+pub const OAK_NUMBER: c_int = 1;
+pub const OAK_STRING: c_int = 2;
+pub const OAK_BOOLEAN: c_int = 3;
+pub const OAK_FUNCTION: c_int = 4;
+pub const OAK_ARRAY: c_int = 5;
+pub const OAK_MAP: c_int = 6;
+pub const OAK_RECORD: c_int = 7;
+pub const OAK_VARIANT: c_int = 8;
+
+/// Capabilities, combined with | for oak_interpreter_new_with_capabilities. The
+/// pure builtins are always available.
+pub const OAK_CAPABILITY_PURE: c_uint = 1;
+pub const OAK_CAPABILITY_CONSOLE: c_uint = 2;
+pub const OAK_CAPABILITY_EXIT: c_uint = 4;
+pub const OAK_CAPABILITY_FILESYSTEM: c_uint = 8;
+pub const OAK_CAPABILITY_ENVIRONMENT: c_uint = 16;
+pub const OAK_CAPABILITY_CLOCK: c_uint = 32;
+pub const OAK_CAPABILITY_RANDOM: c_uint = 64;
+pub const OAK_CAPABILITY_PROCESS: c_uint = 128;
+
+const CAPABILITIES: [(c_uint, Capability); 8] = [
+    (OAK_CAPABILITY_PURE, Capability::Pure),
+    (OAK_CAPABILITY_CONSOLE, Capability::Console),
+    (OAK_CAPABILITY_EXIT, Capability::Exit),
+    (OAK_CAPABILITY_FILESYSTEM, Capability::Filesystem),
+    (OAK_CAPABILITY_ENVIRONMENT, Capability::Environment),
+    (OAK_CAPABILITY_CLOCK, Capability::Clock),
+    (OAK_CAPABILITY_RANDOM, Capability::Random),
+    (OAK_CAPABILITY_PROCESS, Capability::Process),
+];
+
+pub struct OakInterpreter {
+    interpreter: Interpreter,
+    // the last error, kept so that C can borrow its message
+    error: Option<(EvalError, CString)>,
+}
+
+pub struct OakValue {
+    value: Rc<Value>,
+    // the contents of a string value, or the tag of a variant, as a C string
+    string: Option<CString>,
+}
+
+/// A C function registered as an Oak builtin. It is called with the user_data
+/// given to oak_register and the arguments, which are only valid during the call.
+/// It stores a new value in *result (or leaves it NULL, meaning null) and returns
+/// OAK_OK, or returns OAK_ERROR, optionally storing a string value with the error
+/// message in *result. The interpreter frees the stored value.
+pub type OakCallback = unsafe extern "C" fn(
+    user_data: *mut c_void,
+    argc: usize,
+    argv: *const *const OakValue,
+    result: *mut *mut OakValue,
+) -> c_int;
+
+// Strings with embedded NULs are cut off at the first one.
+fn c_string(st: &str) -> CString {
+    let end = st.find('\0').unwrap_or(st.len());
+    CString::new(&st[..end]).expect("the string has no NULs")
+}
+
+fn new_value(value: Rc<Value>) -> *mut OakValue {
+    let string = match &*value {
+        Value::StringType(st) => Some(c_string(st)),
+        Value::Variant(variant) => Some(c_string(variant.tag())),
+        _ => None,
+    };
+    Box::into_raw(Box::new(OakValue { value, string }))
+}
+
+unsafe fn str_arg<'a>(st: *const c_char) -> Option<&'a str> {
+    if st.is_null() {
+        None
+    } else {
+        CStr::from_ptr(st).to_str().ok()
+    }
+}
+
+// Runs the body of an entry point, returning the fallback if it panics.
+fn catch<T>(fallback: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(fallback)
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("no message", String::as_str),
+    }
+}
+
+impl OakInterpreter {
+    // Runs the body of an entry point taking an interpreter, and stores its result
+    // for C, returning its status. A panic becomes a host error.
+    unsafe fn run(
+        interp: *mut OakInterpreter,
+        out: *mut *mut OakValue,
+        body: impl FnOnce(&mut Interpreter) -> Result<Rc<Value>, EvalError>,
+    ) -> c_int {
+        let interp = &mut *interp;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| body(&mut interp.interpreter)))
+            .unwrap_or_else(|payload| {
+                let message = panic_message(&*payload);
+                Err(EvalError::host(format!(
+                    "the interpreter panicked: {}",
+                    message
+                )))
+            });
+        catch(OAK_ERROR, || interp.finish(result, out))
+    }
+
+    // Stores the result of a call for C, returning its status.
+    fn finish(&mut self, result: Result<Rc<Value>, EvalError>, out: *mut *mut OakValue) -> c_int {
+        match result {
+            Ok(val) => {
+                self.error = None;
+                if !out.is_null() {
+                    unsafe { *out = new_value(val) };
+                }
+                OAK_OK
+            }
+            Err(e) => {
+                let status = if e.exit_code().is_some() {
+                    OAK_EXIT
+                } else {
+                    OAK_ERROR
+                };
+                let message = c_string(&e.to_string());
+                self.error = Some((e, message));
+                if !out.is_null() {
+                    unsafe { *out = ptr::null_mut() };
+                }
+                status
+            }
+        }
+    }
+}
+
+// ################################################################
+// #                          INTERPRETER                         #
+// ################################################################
+/// A new interpreter, granting programs the console and exit capabilities (and
+/// the pure builtins).
+#[no_mangle]
+pub extern "C" fn oak_interpreter_new() -> *mut OakInterpreter {
+    oak_interpreter_new_with_capabilities(OAK_CAPABILITY_CONSOLE | OAK_CAPABILITY_EXIT)
+}
+
+/// A new interpreter granting the capabilities in the mask, a combination of the
+/// OAK_CAPABILITY_ constants.
+#[no_mangle]
+pub extern "C" fn oak_interpreter_new_with_capabilities(
+    capabilities: c_uint,
+) -> *mut OakInterpreter {
+    catch(ptr::null_mut(), || {
+        let capabilities = CAPABILITIES
+            .iter()
+            .filter(|(bit, _)| capabilities & bit != 0)
+            .fold(Capabilities::pure(), |caps, &(_, capability)| {
+                caps.with(capability)
+            });
+        Box::into_raw(Box::new(OakInterpreter {
+            interpreter: Interpreter::with_capabilities(capabilities),
+            error: None,
+        }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn oak_interpreter_free(interp: *mut OakInterpreter) {
+    catch((), || {
+        if !interp.is_null() {
+            drop(Box::from_raw(interp));
+        }
+    })
+}
+
+/// Evaluates a program, storing its value in *out (unless out is NULL) on success
+/// and NULL otherwise. Definitions persist from one call to the next.
+#[no_mangle]
+pub unsafe extern "C" fn oak_eval(
+    interp: *mut OakInterpreter,
+    program: *const c_char,
+    out: *mut *mut OakValue,
+) -> c_int {
+    OakInterpreter::run(interp, out, |interpreter| match str_arg(program) {
+        Some(program) => interpreter.eval(program),
+        None => Err(EvalError::host("the program must be UTF-8".to_string())),
+    })
+}
+
+/// Calls the global function with the given name.
+#[no_mangle]
+pub unsafe extern "C" fn oak_call(
+    interp: *mut OakInterpreter,
+    name: *const c_char,
+    argc: usize,
+    argv: *const *const OakValue,
+    out: *mut *mut OakValue,
+) -> c_int {
+    OakInterpreter::run(interp, out, |interpreter| {
+        let args = value_args(argc, argv);
+        match str_arg(name) {
+            Some(name) => match interpreter.get(name) {
+                Some(function) => function.call(args),
+                None => Err(EvalError::unbound(name)),
+            },
+            None => Err(EvalError::host("the name must be UTF-8".to_string())),
+        }
+    })
+}
+
+/// Defines (or replaces) a global. The value is not consumed.
+#[no_mangle]
+pub unsafe extern "C" fn oak_set(
+    interp: *mut OakInterpreter,
+    name: *const c_char,
+    value: *const OakValue,
+) -> c_int {
+    OakInterpreter::run(interp, ptr::null_mut(), |interpreter| {
+        match (str_arg(name), value.as_ref()) {
+            (Some(name), Some(value)) => {
+                interpreter.set(name, Rc::clone(&value.value));
+                Ok(Rc::new(Value::Null))
+            }
+            (None, _) => Err(EvalError::host("the name must be UTF-8".to_string())),
+            (_, None) => Err(EvalError::host("the value must not be NULL".to_string())),
+        }
+    })
+}
+
+/// Returns a new value holding the global, or NULL if it is not defined.
+#[no_mangle]
+pub unsafe extern "C" fn oak_get(
+    interp: *const OakInterpreter,
+    name: *const c_char,
+) -> *mut OakValue {
+    catch(ptr::null_mut(), || {
+        match str_arg(name).and_then(|name| (*interp).interpreter.get(name)) {
+            Some(val) => new_value(val),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+unsafe fn value_args(argc: usize, argv: *const *const OakValue) -> Vec<Rc<Value>> {
+    if argc == 0 || argv.is_null() {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(argv, argc)
+        .iter()
+        .map(|&arg| match arg.as_ref() {
+            Some(arg) => Rc::clone(&arg.value),
+            None => Rc::new(Value::Null),
+        })
+        .collect()
+}
+
+/// Defines a global function taking `arity` arguments which calls the callback.
+#[no_mangle]
+pub unsafe extern "C" fn oak_register(
+    interp: *mut OakInterpreter,
+    name: *const c_char,
+    arity: usize,
+    callback: OakCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    OakInterpreter::run(interp, ptr::null_mut(), |interpreter| {
+        let name = match str_arg(name) {
+            Some(name) => name,
+            None => return Err(EvalError::host("the name must be UTF-8".to_string())),
+        };
+        let parameters: Vec<String> = (0..arity).map(|i| format!("arg{}", i)).collect();
+        let parameters: Vec<&str> = parameters.iter().map(String::as_str).collect();
+        let function_name = name.to_string();
+
+        let function = HostFunction::new(&parameters, move |args| {
+            let values: Vec<*mut OakValue> = (0..args.len())
+                .map(|position| args.get::<Rc<Value>>(position).map(new_value))
+                .collect::<Result<_, _>>()?;
+            let mut result: *mut OakValue = ptr::null_mut();
+            let status = callback(
+                user_data,
+                values.len(),
+                values.as_ptr() as *const *const OakValue,
+                &mut result,
+            );
+            for value in values {
+                oak_value_free(value);
+            }
+
+            let value = if result.is_null() {
+                Rc::new(Value::Null)
+            } else {
+                Box::from_raw(result).value
+            };
+            if status == OAK_OK {
+                Ok(value)
+            } else {
+                match &*value {
+                    Value::StringType(message) => Err(EvalError::host(message.clone())),
+                    _ => Err(EvalError::host(format!("{} failed", function_name))),
+                }
+            }
+        });
+        interpreter.register(name, function);
+        Ok(Rc::new(Value::Null))
+    })
+}
+
+// ################################################################
+// #                            ERRORS                            #
+// ################################################################
+/// The message of the last error, or NULL if the last call on the interpreter
+/// succeeded. It is valid until the next call on the interpreter. A panic inside
+/// the library is an error too, rather than unwinding into C.
+#[no_mangle]
+pub unsafe extern "C" fn oak_error_message(interp: *const OakInterpreter) -> *const c_char {
+    catch(ptr::null(), || match &(*interp).error {
+        Some((_, message)) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// The line of the last error in the program, or 0 if it has no location.
+#[no_mangle]
+pub unsafe extern "C" fn oak_error_line(interp: *const OakInterpreter) -> usize {
+    catch(0, || match &(*interp).error {
+        Some((e, _)) => e.location.map_or(0, |location| location.line),
+        None => 0,
+    })
+}
+
+/// The column of the last error in the program, or 0 if it has no location.
+#[no_mangle]
+pub unsafe extern "C" fn oak_error_column(interp: *const OakInterpreter) -> usize {
+    catch(0, || match &(*interp).error {
+        Some((e, _)) => e.location.map_or(0, |location| location.column),
+        None => 0,
+    })
+}
+
+/// The code the program passed to exit, after a call returned OAK_EXIT.
+#[no_mangle]
+pub unsafe extern "C" fn oak_exit_code(interp: *const OakInterpreter) -> c_int {
+    catch(0, || match &(*interp).error {
+        Some((e, _)) => e.exit_code().unwrap_or(0),
+        None => 0,
+    })
+}
+
+// ################################################################
+// #                            VALUES                            #
+// ################################################################
+#[no_mangle]
+pub extern "C" fn oak_value_null() -> *mut OakValue {
+    catch(ptr::null_mut(), || new_value(Rc::new(Value::Null)))
+}
+
+#[no_mangle]
+pub extern "C" fn oak_value_number(num: c_double) -> *mut OakValue {
+    catch(ptr::null_mut(), || new_value(Rc::new(Value::Number(num))))
+}
+
+#[no_mangle]
+pub extern "C" fn oak_value_integer(num: c_longlong) -> *mut OakValue {
+    catch(ptr::null_mut(), || {
+        new_value(Rc::new(Value::Integer(BigInt::from(num))))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn oak_value_boolean(b: c_int) -> *mut OakValue {
+    catch(ptr::null_mut(), || {
+        new_value(Rc::new(Value::Boolean(b != 0)))
+    })
+}
+
+/// Copies a UTF-8 string into a new value; returns NULL if it isn't valid UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_string(st: *const c_char) -> *mut OakValue {
+    catch(ptr::null_mut(), || match str_arg(st) {
+        Some(st) => new_value(Rc::new(Value::StringType(st.to_string()))),
+        None => ptr::null_mut(),
+    })
+}
+
+/// Creates an array of the given values, which are not consumed.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_array(
+    len: usize,
+    items: *const *const OakValue,
+) -> *mut OakValue {
+    catch(ptr::null_mut(), || {
+        new_value(Rc::new(Value::Array(value_args(len, items))))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_free(value: *mut OakValue) {
+    catch((), || {
+        if !value.is_null() {
+            drop(Box::from_raw(value));
+        }
+    })
+}
+
+/// The type of the value: OAK_NULL, OAK_NUMBER and so on.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_type(value: *const OakValue) -> c_int {
+    catch(OAK_NULL, || match *(*value).value {
+        Value::Null => OAK_NULL,
+        Value::Number(_) | Value::Integer(_) | Value::Rational(_) | Value::Decimal(_) => OAK_NUMBER,
+        Value::StringType(_) => OAK_STRING,
+        Value::Boolean(_) => OAK_BOOLEAN,
+        Value::Function(_) => OAK_FUNCTION,
+        Value::Array(_) => OAK_ARRAY,
+        Value::Map(_) => OAK_MAP,
+        Value::Record(_) => OAK_RECORD,
+        Value::Variant(_) => OAK_VARIANT,
+    })
+}
+
+/// The number, or NaN if the value isn't a number. Integers are rounded to the
+/// nearest double.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_as_number(value: *const OakValue) -> c_double {
+    catch(f64::NAN, || match Number::from_value(&(*value).value) {
+        Some(num) => num.to_f64(),
+        None => f64::NAN,
+    })
+}
+
+/// Stores an integral number in *out and returns OAK_OK, or returns OAK_ERROR if
+/// the value isn't one or doesn't fit in a long long.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_as_integer(
+    value: *const OakValue,
+    out: *mut c_longlong,
+) -> c_int {
+    catch(OAK_ERROR, || {
+        let int = Number::from_value(&(*value).value)
+            .and_then(|num| num.to_integer())
+            .and_then(|int| int.to_i64());
+        match int {
+            Some(int) => {
+                *out = int;
+                OAK_OK
+            }
+            None => OAK_ERROR,
+        }
+    })
+}
+
+/// 1 for true, 0 for false or anything that isn't a boolean.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_as_boolean(value: *const OakValue) -> c_int {
+    catch(0, || match *(*value).value {
+        Value::Boolean(b) => b as c_int,
+        _ => 0,
+    })
+}
+
+/// The contents of a string value, or NULL for other values. It is valid for as
+/// long as the value.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_as_string(value: *const OakValue) -> *const c_char {
+    catch(ptr::null(), || match (&*(*value).value, &(*value).string) {
+        (Value::StringType(_), Some(st)) => st.as_ptr(),
+        _ => ptr::null(),
+    })
+}
+
+/// The tag of a variant, or NULL for other values. It is valid for as long as the
+/// value.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_tag(value: *const OakValue) -> *const c_char {
+    catch(ptr::null(), || match (&*(*value).value, &(*value).string) {
+        (Value::Variant(_), Some(tag)) => tag.as_ptr(),
+        _ => ptr::null(),
+    })
+}
+
+/// The number of elements in an array, entries in a map, fields in a record or
+/// values in the payload of a variant, or 0 for other values.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_length(value: *const OakValue) -> usize {
+    catch(0, || match &*(*value).value {
+        Value::Array(elements) => elements.len(),
+        Value::Map(map) => map.len(),
+        Value::Record(record) => record.len(),
+        Value::Variant(variant) => variant.payload().len(),
+        _ => 0,
+    })
+}
+
+/// Returns a new value holding the element of an array (or payload value of a
+/// variant) at the index, or NULL if the index is out of range or the value is
+/// neither.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_index(value: *const OakValue, index: usize) -> *mut OakValue {
+    catch(ptr::null_mut(), || {
+        let elements = match &*(*value).value {
+            Value::Array(elements) => elements.as_slice(),
+            Value::Variant(variant) => variant.payload(),
+            _ => return ptr::null_mut(),
+        };
+        match elements.get(index) {
+            Some(element) => new_value(Rc::clone(element)),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// Returns a new value holding the entry of a map with the key, or the field of a
+/// record named by a string key, or NULL if there is no such entry or field or
+/// the value is neither.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_get(
+    value: *const OakValue,
+    key: *const OakValue,
+) -> *mut OakValue {
+    catch(ptr::null_mut(), || {
+        let key = &(*key).value;
+        let entry = match (&*(*value).value, &**key) {
+            (Value::Map(map), _) => map.get(&Key::new(Rc::clone(key))),
+            (Value::Record(record), Value::StringType(name)) => record.value(name),
+            _ => None,
+        };
+        match entry {
+            Some(entry) => new_value(Rc::clone(entry)),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// The value as Oak prints it, to be freed with oak_string_free.
+#[no_mangle]
+pub unsafe extern "C" fn oak_value_to_string(value: *const OakValue) -> *mut c_char {
+    catch(ptr::null_mut(), || {
+        c_string(&(*value).value.to_string()).into_raw()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn oak_string_free(st: *mut c_char) {
+    catch((), || {
+        if !st.is_null() {
+            drop(CString::from_raw(st));
+        }
+    })
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(st: &str) -> CString {
+        CString::new(st).unwrap()
+    }
+
+    unsafe extern "C" fn negate(
+        _user_data: *mut c_void,
+        _argc: usize,
+        argv: *const *const OakValue,
+        result: *mut *mut OakValue,
+    ) -> c_int {
+        match oak_value_type(*argv) {
+            OAK_NUMBER => {
+                *result = oak_value_number(-oak_value_as_number(*argv));
+                OAK_OK
+            }
+            _ => {
+                *result = oak_value_string(c("negate takes a number").as_ptr());
+                OAK_ERROR
+            }
+        }
+    }
+
+    #[test]
+    fn grants_capabilities_and_catches_panics() {
+        unsafe {
+            let interp = oak_interpreter_new_with_capabilities(OAK_CAPABILITY_CONSOLE);
+            let mut out = ptr::null_mut();
+            assert_eq!(
+                oak_eval(interp, c("(exit 1)").as_ptr(), &mut out),
+                OAK_ERROR
+            );
+            let message = CStr::from_ptr(oak_error_message(interp)).to_str().unwrap();
+            assert!(message.contains("capability not granted"), "{}", message);
+
+            let status = OakInterpreter::run(interp, &mut out, |_| panic!("at the disco"));
+            assert_eq!(status, OAK_ERROR);
+            assert!(out.is_null());
+            let message = CStr::from_ptr(oak_error_message(interp)).to_str().unwrap();
+            assert_eq!(message, "HostError: the interpreter panicked: at the disco");
+            // the interpreter is still usable
+            assert_eq!(oak_eval(interp, c("(+ 1 2)").as_ptr(), &mut out), OAK_OK);
+            assert_eq!(oak_value_as_number(out), 3.0);
+            oak_value_free(out);
+            oak_interpreter_free(interp);
+        }
+        assert_eq!(catch(OAK_ERROR, || panic!("{}", 1)), OAK_ERROR);
+    }
+
+    #[test]
+    fn header_matches_the_c_api() {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir)).unwrap();
+        let mut header = Vec::new();
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/ffi.rs", dir))
+            .generate()
+            .unwrap()
+            .write(&mut header);
+        let path = format!("{}/include/oak.h", dir);
+        if std::env::var_os("OAK_WRITE_HEADER").is_some() {
+            std::fs::write(&path, &header).unwrap();
+        }
+        assert!(
+            std::fs::read(&path).unwrap() == header,
+            "include/oak.h is out of date; run the tests with OAK_WRITE_HEADER=1"
+        );
+    }
+
+    #[test]
+    fn embeds_through_the_c_api() {
+        unsafe {
+            let interp = oak_interpreter_new();
+            oak_register(interp, c("negate").as_ptr(), 1, negate, ptr::null_mut());
+
+            let mut out = ptr::null_mut();
+            assert_eq!(oak_eval(interp, c("(negate 2)").as_ptr(), &mut out), OAK_OK);
+            assert_eq!(oak_value_as_number(out), -2.0);
+            oak_value_free(out);
+
+            let arg = oak_value_string(c("oak").as_ptr());
+            assert_eq!(
+                oak_call(interp, c("type").as_ptr(), 1, &(arg as *const _), &mut out),
+                OAK_OK
+            );
+            assert_eq!(
+                CStr::from_ptr(oak_value_as_string(out)).to_str(),
+                Ok("string")
+            );
+            oak_value_free(out);
+            oak_value_free(arg);
+
+            assert_eq!(
+                oak_eval(interp, c("(negate 'x')").as_ptr(), &mut out),
+                OAK_ERROR
+            );
+            assert!(out.is_null());
+            let message = CStr::from_ptr(oak_error_message(interp)).to_str().unwrap();
+            assert_eq!(message, "HostError: negate takes a number (at 1:1)");
+            assert_eq!(oak_eval(interp, c("(exit 4)").as_ptr(), &mut out), OAK_EXIT);
+            assert_eq!(oak_exit_code(interp), 4);
+
+            oak_interpreter_free(interp);
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use crate::common::*;
+use crate::convert::*;
+use crate::expressions::*;
+use std::fmt;
+use std::rc::Rc;
+
+// The value returned by a host function: anything that converts to Oak, or a
+// Result of one, whose error becomes an Oak error.
+pub trait IntoOakResult {
+    fn into_oak_result(self) -> Result<Rc<Value>, EvalError>;
+}
+
+impl<T: IntoOak> IntoOakResult for T {
+    fn into_oak_result(self) -> Result<Rc<Value>, EvalError> {
+        Ok(self.into_oak())
+    }
+}
+
+impl<T: IntoOak, E: Into<EvalError>> IntoOakResult for Result<T, E> {
+    fn into_oak_result(self) -> Result<Rc<Value>, EvalError> {
+        self.map(IntoOak::into_oak).map_err(Into::into)
+    }
+}
+
+// The arguments a host function was called with.
+pub struct Arguments<'a> {
+    function: &'a str,
+    arity: usize,
+    values: &'a [Rc<Value>],
+    null: &'a Rc<Value>,
+}
+
+// The error for an argument of the wrong type, which builtins that check their
+// own arguments share with host functions.
+pub fn argument_error(function: &str, position: usize, expected: &str, value: &Value) -> EvalError {
+    EvalError::type_error(
+        expected,
+        value,
+        Some(position),
+        format!(
+            "argument {} of {} must be a {}, not a {}!",
+            position + 1,
+            function,
+            expected,
+            value.type_name()
+        ),
+    )
+}
+
+impl<'a> Arguments<'a> {
+    // Converts the argument at the given position. A missing argument is treated
+    // as null (so Option parameters are optional); if the type doesn't accept
+    // null, that is an arity error.
+    pub fn get<T: FromOak<'a>>(&self, position: usize) -> Result<T, EvalError> {
+        let value = self.values.get(position).unwrap_or(self.null);
+
+        T::from_oak(value).map_err(|e| match e.kind {
+            _ if position >= self.values.len() => EvalError::new(
+                ErrorKind::Arity {
+                    expected: self.arity,
+                    actual: self.values.len(),
+                },
+                format!(
+                    "{} takes {} arguments, but was given {}!",
+                    self.function,
+                    self.arity,
+                    self.values.len()
+                ),
+            ),
+            ErrorKind::Type { expected, .. } => {
+                argument_error(self.function, position, &expected, value)
+            }
+            _ => e,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+// A Rust closure that can be called from Oak with typed arguments, for example
+// `|x: f64, name: String| -> Result<String, EvalError>`. Arguments must be owned
+// types; use host_fn! for functions that borrow them (such as `&str`).
+pub trait HostFn<Args>: 'static {
+    fn arity(&self) -> usize;
+    fn invoke(&self, args: &Arguments) -> Result<Rc<Value>, EvalError>;
+}
+
+macro_rules! impl_host_fn {
+    ($arity:expr; $($arg:ident $position:tt),*) => {
+        impl<Func, Ret, $($arg),*> HostFn<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + 'static,
+            Ret: IntoOakResult,
+            $($arg: for<'a> FromOak<'a>,)*
+        {
+            fn arity(&self) -> usize {
+                $arity
+            }
+
+            #[allow(unused_variables)]
+            fn invoke(&self, args: &Arguments) -> Result<Rc<Value>, EvalError> {
+                (self)($(args.get::<$arg>($position)?),*).into_oak_result()
+            }
+        }
+    };
+}
+
+impl_host_fn!(0;);
+impl_host_fn!(1; A 0);
+impl_host_fn!(2; A 0, B 1);
+impl_host_fn!(3; A 0, B 1, C 2);
+impl_host_fn!(4; A 0, B 1, C 2, D 3);
+impl_host_fn!(5; A 0, B 1, C 2, D 3, E 4);
+impl_host_fn!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+
+type HostClosure = dyn Fn(&Arguments) -> Result<Rc<Value>, EvalError>;
+
+// A function implemented in Rust. It is the body of an ordinary Oak function
+// value, so Oak code can't tell it apart from a function written in Oak, except
+// that host functions check how many arguments they are given.
+pub struct HostFunction {
+    name: String,
+    parameters: Vec<String>,
+    function: Box<HostClosure>,
+}
+
+impl HostFunction {
+    // Creates a host function from a closure that converts its own arguments.
+    pub fn new<F>(parameters: &[&str], function: F) -> HostFunction
+    where
+        F: Fn(&Arguments) -> Result<Rc<Value>, EvalError> + 'static,
+    {
+        HostFunction {
+            name: "host function".to_string(),
+            parameters: parameters.iter().map(|p| p.to_string()).collect(),
+            function: Box::new(function),
+        }
+    }
+
+    // Creates a host function from a typed closure. Its parameters are named arg0,
+    // arg1, etc.
+    pub fn from_fn<Args, F: HostFn<Args>>(function: F) -> HostFunction {
+        let parameters: Vec<String> = (0..function.arity()).map(|i| format!("arg{}", i)).collect();
+        HostFunction {
+            name: "host function".to_string(),
+            parameters,
+            function: Box::new(move |args| function.invoke(args)),
+        }
+    }
+
+    // Names the function, for error messages.
+    pub fn named(mut self, name: &str) -> HostFunction {
+        self.name = name.to_string();
+        self
+    }
+
+    // Wraps the host function in an Oak function value.
+    pub fn into_value(self) -> Value {
+        Value::Function(Function {
+            parameters: self.parameters.clone(),
+            parameter_types: Vec::new(),
+            body: Rc::new(self),
+            closure: Some(Rc::new(Scope::new(None))),
+        })
+    }
+}
+
+// Shorthand for HostFunction::from_fn(function).named(name).into_value().
+pub fn function<Args, F: HostFn<Args>>(name: &str, function: F) -> Value {
+    HostFunction::from_fn(function).named(name).into_value()
+}
+
+impl Expression for HostFunction {
+    fn evaluate(&self, scope: Rc<Scope>, _pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
+        // Function::call bound the arguments to the parameters in the call's own
+        // scope (ignoring any extra ones); any missing ones are at the end.
+        let values: Vec<Rc<Value>> = self
+            .parameters
+            .iter()
+            .map_while(|param| scope.lookup_local(param))
+            .collect();
+        let null = Rc::new(Value::Null);
+        let args = Arguments {
+            function: &self.name,
+            arity: self.parameters.len(),
+            values: &values,
+            null: &null,
+        };
+
+        (self.function)(&args)
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostFunction({})", self.name)
+    }
+}
+
+// Creates a HostFunction from a closure with typed parameters, which may borrow
+// from the arguments, and an optional return type. The parameter names are used
+// as the names of the Oak function's parameters.
+//
+//     host_fn!(|name: &str, times: f64| -> String { name.repeat(times as usize) })
+#[macro_export]
+macro_rules! host_fn {
+    (|$($param:ident : $ty:ty),* $(,)?| -> $ret:ty $body:block) => {
+        $crate::HostFunction::new(&[$(stringify!($param)),*], move |args| {
+            $crate::host_fn!(@bind args, 0; $($param : $ty),*);
+            let result: $ret = $body;
+            $crate::IntoOakResult::into_oak_result(result)
+        })
+    };
+    (|$($param:ident : $ty:ty),* $(,)?| $body:expr) => {
+        $crate::HostFunction::new(&[$(stringify!($param)),*], move |args| {
+            $crate::host_fn!(@bind args, 0; $($param : $ty),*);
+            $crate::IntoOakResult::into_oak_result($body)
+        })
+    };
+    (@bind $args:ident, $position:expr;) => {};
+    (@bind $args:ident, $position:expr; $param:ident : $ty:ty $(, $rest:ident : $rest_ty:ty)*) => {
+        let $param: $ty = $args.get($position)?;
+        $crate::host_fn!(@bind $args, $position + 1; $($rest : $rest_ty),*);
+    };
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use crate::interpreter::Interpreter;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn calls_typed_host_functions() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register(
+            "greet",
+            host_fn!(|name: &str, times: f64| -> String {
+                format!("hi {}", name).repeat(times as usize)
+            }),
+        );
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        interpreter.register_fn("count", move || {
+            counter.set(counter.get() + 1);
+            counter.get() as f64
+        });
+
+        let result = interpreter.eval("(count) (greet 'oak' 2)").unwrap();
+        assert_eq!(*result, Value::StringType("hi oakhi oak".to_string()));
+        assert_eq!(calls.get(), 1);
+
+        let err = interpreter.eval("(greet 2 'oak')").unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::Type {
+                position: Some(0),
+                ..
+            }
+        ));
+        // a missing argument isn't taken from a variable with the parameter's name
+        let err = interpreter
+            .eval("(def .'times' 3) (greet 'oak')")
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::Arity {
+                expected: 2,
+                actual: 1
+            }
+        ));
+        // and extra arguments are ignored, as by Oak functions
+        let result = interpreter.eval("(count 1)").unwrap();
+        assert_eq!(*result, Value::Number(2.0));
+    }
+}
@@ -0,0 +1,756 @@
+use crate::capability::Capabilities;
+use crate::check;
+use crate::common::*;
+use crate::expressions::*;
+use crate::output::Output;
+use crate::stdlib;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// Optional static type inference, in the style of Hindley–Milner: every
+// expression gets a type, calls unify the types of the arguments with those of
+// the parameters, and names defined with def are generalized, so that they can
+// be used at different types.
+//
+// Oak is dynamic, and programs mix types in ways a strict checker would reject.
+// Those places get the type `any`, which is compatible with every type: the
+// builtins that accept many kinds of values, array and map literals with mixed
+// elements, `if`s and matches whose results differ, values taken apart by a
+// pattern and undefined names. So only definite conflicts are reported.
+pub fn check_types(expressions: &[Rc<dyn Expression>]) -> Vec<EvalError> {
+    let mut inferer = Inferer::new();
+
+    // Names def'd anywhere are known from the start (as check does), so that
+    // functions can refer to names defined after them.
+    let mut names = Vec::new();
+    for expr in stdlib::get_prelude().iter().chain(expressions) {
+        collect_defs(&**expr, &mut names);
+    }
+    for name in names {
+        let var = inferer.fresh();
+        inferer.pending.insert(name.clone(), var.clone());
+        inferer.scopes[0].insert(name, Scheme::monomorphic(var));
+    }
+
+    for expr in stdlib::get_prelude() {
+        inferer.infer(&*expr, &Type::Null);
+    }
+    inferer.errors.clear();
+
+    let mut pipe = Type::Null;
+    for expr in expressions {
+        pipe = inferer.infer(&**expr, &pipe);
+    }
+    inferer.errors
+}
+
+fn collect_defs(expr: &dyn Expression, names: &mut Vec<String>) {
+    if let Node::Call(call) = expr.node() {
+        if let Some(name) = check::def_name(call) {
+            names.push(name.to_string());
+        }
+    }
+    check::for_each_child(expr, |child| collect_defs(child, names));
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    // a type that is not known yet
+    Var(usize),
+    // any type at all, for values whose type can't be tracked
+    Any,
+    Number,
+    String,
+    Boolean,
+    Null,
+    Array(Box<Type>),
+    // a map with values of the type (keys are not tracked)
+    Map(Box<Type>),
+    Record,
+    Variant,
+    Function(Vec<Type>, Box<Type>),
+}
+
+fn function(parameters: Vec<Type>, result: Type) -> Type {
+    Type::Function(parameters, Box::new(result))
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Var(var) => write!(f, "t{}", var),
+            Type::Any => f.write_str("any"),
+            Type::Number => f.write_str("number"),
+            Type::String => f.write_str("string"),
+            Type::Boolean => f.write_str("boolean"),
+            Type::Null => f.write_str("null"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Map(value) => write!(f, "#{{{}}}", value),
+            Type::Record => f.write_str("record"),
+            Type::Variant => f.write_str("variant"),
+            Type::Function(parameters, result) => {
+                let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+                write!(f, "(/{} .{})", parameters.join(" "), result)
+            }
+        }
+    }
+}
+
+// A type that can be used at different types for the variables in `vars`.
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Type,
+}
+
+impl Scheme {
+    fn monomorphic(ty: Type) -> Self {
+        Scheme {
+            vars: Vec::new(),
+            ty,
+        }
+    }
+}
+
+struct Inferer {
+    // the type each variable has been unified with, if any
+    substitution: Vec<Option<Type>>,
+    // the variables bound by unify, so that a failed unification can be undone
+    trail: Vec<usize>,
+    // the types of names, innermost scope last; the first scope holds the
+    // builtins and names defined with def
+    scopes: Vec<HashMap<String, Scheme>>,
+    // names that will be def'd, but haven't been yet, with their type so far
+    pending: HashMap<String, Type>,
+    errors: Vec<EvalError>,
+    // the location of the innermost call being inferred
+    location: Option<Location>,
+}
+
+impl Inferer {
+    fn new() -> Self {
+        let mut inferer = Inferer {
+            substitution: Vec::new(),
+            trail: Vec::new(),
+            scopes: vec![HashMap::new()],
+            pending: HashMap::new(),
+            errors: Vec::new(),
+            location: None,
+        };
+        let mut builtins = Scope::new(None);
+        stdlib::insert_stdlib(&mut builtins, &Output::default(), Capabilities::all());
+        for name in builtins.names().into_iter().chain(Some("args".to_string())) {
+            let var = inferer.fresh();
+            let scheme = match (builtin_type(&name, &var), var) {
+                (Some(ty), Type::Var(var)) => Scheme {
+                    vars: vec![var],
+                    ty,
+                },
+                _ => Scheme::monomorphic(Type::Any),
+            };
+            inferer.scopes[0].insert(name, scheme);
+        }
+        inferer
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.substitution.push(None);
+        Type::Var(self.substitution.len() - 1)
+    }
+
+    // Follows bound variables at the top of the type.
+    fn shallow(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(var) => match &self.substitution[*var] {
+                Some(bound) => self.shallow(bound),
+                None => ty.clone(),
+            },
+            other => other.clone(),
+        }
+    }
+
+    // The type with all bound variables replaced.
+    fn resolve(&self, ty: &Type) -> Type {
+        match self.shallow(ty) {
+            Type::Array(element) => Type::Array(Box::new(self.resolve(&element))),
+            Type::Map(value) => Type::Map(Box::new(self.resolve(&value))),
+            Type::Function(parameters, result) => function(
+                parameters.iter().map(|p| self.resolve(p)).collect(),
+                self.resolve(&result),
+            ),
+            other => other,
+        }
+    }
+
+    fn occurs(&self, var: usize, ty: &Type) -> bool {
+        match self.shallow(ty) {
+            Type::Var(other) => other == var,
+            Type::Array(inner) | Type::Map(inner) => self.occurs(var, &inner),
+            Type::Function(parameters, result) => {
+                parameters.iter().any(|p| self.occurs(var, p)) || self.occurs(var, &result)
+            }
+            _ => false,
+        }
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        let mark = self.trail.len();
+        let unified = self.unify_inner(a, b);
+        if !unified {
+            self.undo(mark);
+        }
+        unified
+    }
+
+    fn undo(&mut self, mark: usize) {
+        for var in self.trail.drain(mark..) {
+            self.substitution[var] = None;
+        }
+    }
+
+    fn unify_inner(&mut self, a: &Type, b: &Type) -> bool {
+        let (a, b) = (self.shallow(a), self.shallow(b));
+        match (&a, &b) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Var(x), Type::Var(y)) if x == y => true,
+            (Type::Var(var), other) | (other, Type::Var(var)) => {
+                // recursive types (a function applied to itself, say) are left
+                // unchecked
+                if !self.occurs(*var, other) {
+                    self.substitution[*var] = Some(other.clone());
+                    self.trail.push(*var);
+                }
+                true
+            }
+            (Type::Array(x), Type::Array(y)) | (Type::Map(x), Type::Map(y)) => {
+                self.unify_inner(x, y)
+            }
+            // Functions may be given fewer arguments than they have parameters,
+            // or more (which are ignored), so only the common ones are unified.
+            (Type::Function(p1, r1), Type::Function(p2, r2)) => {
+                p1.iter().zip(p2).all(|(x, y)| self.unify_inner(x, y)) && self.unify_inner(r1, r2)
+            }
+            _ => a == b,
+        }
+    }
+
+    // The type of a value that may come from any of the types: that type if they
+    // are all the same, or any otherwise. Unknown types are not unified, as the
+    // branches of an `if` (say) don't need to have the same type.
+    fn join(&mut self, types: &[Type]) -> Type {
+        let types: Vec<Type> = types.iter().map(|ty| self.resolve(ty)).collect();
+        match types.split_first() {
+            Some((first, others)) if others.iter().all(|ty| ty == first) => first.clone(),
+            Some(_) => Type::Any,
+            None => self.fresh(),
+        }
+    }
+
+    fn free_vars(&self, ty: &Type, quantified: &[usize], out: &mut Vec<usize>) {
+        match ty {
+            Type::Var(var) if quantified.contains(var) => (),
+            Type::Var(var) => match &self.substitution[*var] {
+                Some(bound) => self.free_vars(bound, quantified, out),
+                None => out.push(*var),
+            },
+            Type::Array(inner) | Type::Map(inner) => self.free_vars(inner, quantified, out),
+            Type::Function(parameters, result) => {
+                for parameter in parameters {
+                    self.free_vars(parameter, quantified, out);
+                }
+                self.free_vars(result, quantified, out);
+            }
+            _ => (),
+        }
+    }
+
+    // Quantifies over the variables of the type that aren't used by any name in
+    // scope.
+    fn generalize(&self, ty: &Type) -> Scheme {
+        let mut in_scope = Vec::new();
+        for scheme in self.scopes.iter().flat_map(|scope| scope.values()) {
+            self.free_vars(&scheme.ty, &scheme.vars, &mut in_scope);
+        }
+        let mut vars = Vec::new();
+        self.free_vars(ty, &[], &mut vars);
+        vars.retain(|var| !in_scope.contains(var));
+        vars.dedup();
+        Scheme {
+            vars,
+            ty: self.resolve(ty),
+        }
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        let fresh: HashMap<usize, Type> =
+            scheme.vars.iter().map(|var| (*var, self.fresh())).collect();
+        replace_vars(&scheme.ty, &fresh)
+    }
+
+    fn lookup(&mut self, name: &str) -> Type {
+        let scheme = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned();
+        match scheme {
+            Some(scheme) => self.instantiate(&scheme),
+            // undefined names are reported by check, and evaluate to null
+            None => Type::Any,
+        }
+    }
+
+    fn error(&mut self, expected: &Type, actual: &Type, position: Option<usize>, reason: String) {
+        let kind = ErrorKind::Type {
+            expected: self.resolve(expected).to_string(),
+            actual: self.resolve(actual).to_string(),
+            position,
+        };
+        let mut error = EvalError::new(kind, reason);
+        if let Some(location) = self.location {
+            error = error.at(location);
+        }
+        self.errors.push(error);
+    }
+
+    fn infer(&mut self, expr: &dyn Expression, pipe: &Type) -> Type {
+        match expr.node() {
+            Node::Literal(literal) => match &*literal.value {
+                Value::Function(fn_obj) => self.infer_function(fn_obj),
+                Value::StringType(_) => Type::String,
+                Value::Boolean(_) => Type::Boolean,
+                Value::Null => Type::Null,
+                value if value.type_name() == "number" => Type::Number,
+                _ => Type::Any,
+            },
+            Node::Identifier(id) if id.name == "^" => pipe.clone(),
+            Node::Identifier(id) => self.lookup(&id.name),
+            Node::Block(block) => {
+                let mut ty = pipe.clone();
+                for expr in &block.expressions {
+                    ty = self.infer(&**expr, &ty);
+                }
+                ty
+            }
+            Node::Call(call) => {
+                let outer = self.location;
+                self.location = call.location.or(outer);
+                let ty = self.infer_call(call, pipe);
+                self.location = outer;
+                ty
+            }
+            Node::Array(array) => {
+                let elements: Vec<Type> = array
+                    .elements
+                    .iter()
+                    .map(|element| self.infer(&**element, pipe))
+                    .collect();
+                Type::Array(Box::new(self.join(&elements)))
+            }
+            Node::Map(map) => {
+                let mut values = Vec::new();
+                for (key, value) in &map.entries {
+                    self.infer(&**key, pipe);
+                    values.push(self.infer(&**value, pipe));
+                }
+                Type::Map(Box::new(self.join(&values)))
+            }
+            Node::Record(record) => {
+                for (_, value) in &record.fields {
+                    self.infer(&**value, pipe);
+                }
+                Type::Record
+            }
+            Node::Match(matcher) => {
+                let outer = self.location;
+                self.location = matcher.location.or(outer);
+                let ty = self.infer_match(matcher, pipe);
+                self.location = outer;
+                ty
+            }
+            Node::Native => Type::Any,
+        }
+    }
+
+    // The type of a value annotated with the type name (`x:number`).
+    fn annotation_type(&mut self, annotation: &str) -> Type {
+        match annotation {
+            "number" => Type::Number,
+            "string" => Type::String,
+            "boolean" => Type::Boolean,
+            "null" => Type::Null,
+            "array" => Type::Array(Box::new(self.fresh())),
+            "map" => Type::Map(Box::new(self.fresh())),
+            "record" => Type::Record,
+            "variant" => Type::Variant,
+            // a function of any arity, as only the common parameters are unified
+            "function" => function(Vec::new(), self.fresh()),
+            _ => Type::Any,
+        }
+    }
+
+    fn infer_function(&mut self, fn_obj: &Function) -> Type {
+        let types: Vec<Type> = (0..fn_obj.parameters.len())
+            .map(|position| match fn_obj.parameter_type(position) {
+                Some(annotation) => self.annotation_type(annotation),
+                None => self.fresh(),
+            })
+            .collect();
+        let scope = fn_obj
+            .parameters
+            .iter()
+            .cloned()
+            .zip(types.iter().cloned().map(Scheme::monomorphic))
+            .collect();
+        self.scopes.push(scope);
+        // function bodies are evaluated with null as the pipe value
+        let result = self.infer(&*fn_obj.body, &Type::Null);
+        self.scopes.pop();
+        function(types, result)
+    }
+
+    fn infer_call(&mut self, call: &FunctionExpression, pipe: &Type) -> Type {
+        let name = match call.function.node() {
+            Node::Identifier(id) => Some(id.name.as_str()),
+            _ => None,
+        };
+        match name {
+            Some("def") => return self.infer_def(call, pipe),
+            Some("if") => return self.infer_if(call, pipe),
+            Some(name @ ("and" | "or")) => return self.infer_logical(name, call, pipe),
+            _ => (),
+        }
+
+        let callee = self.infer(&*call.function, pipe);
+        let arguments: Vec<Type> = call
+            .arguments
+            .iter()
+            .map(|arg| self.infer(&**arg, pipe))
+            .collect();
+
+        match self.shallow(&callee) {
+            Type::Function(parameters, result) => {
+                for (position, (parameter, argument)) in
+                    parameters.iter().zip(&arguments).enumerate()
+                {
+                    if !self.unify(parameter, argument) {
+                        let (expected, actual) = (self.resolve(parameter), self.resolve(argument));
+                        let reason = match name {
+                            Some(name) => format!(
+                                "argument {} of {} must be a {}, not a {}!",
+                                position + 1,
+                                name,
+                                expected,
+                                actual
+                            ),
+                            None => format!(
+                                "argument {} must be a {}, not a {}!",
+                                position + 1,
+                                expected,
+                                actual
+                            ),
+                        };
+                        self.error(parameter, argument, Some(position), reason);
+                    }
+                }
+                *result
+            }
+            Type::Array(element) => *element,
+            Type::Map(value) => *value,
+            // arrays, maps, records and variants can be called too, so calling a
+            // value of unknown type doesn't make it a function
+            Type::Var(_) | Type::Record | Type::Variant | Type::Any => Type::Any,
+            other => {
+                self.error(
+                    &function(Vec::new(), Type::Any),
+                    &other,
+                    None,
+                    format!(
+                        "the first item in a function expression is a {}, not a function!",
+                        other
+                    ),
+                );
+                Type::Any
+            }
+        }
+    }
+
+    // `(def .'name' value)` gives the name the value's type, generalized.
+    // If a type is given as a string literal, the value must have it.
+    fn infer_def(&mut self, call: &FunctionExpression, pipe: &Type) -> Type {
+        let value = match call.arguments.get(1) {
+            Some(value) => self.infer(&**value, pipe),
+            None => Type::Null,
+        };
+        let annotation = call.arguments.get(2).and_then(|arg| match arg.node() {
+            Node::Literal(literal) => match &*literal.value {
+                Value::StringType(annotation) => type_name(annotation),
+                _ => None,
+            },
+            _ => None,
+        });
+        if let Some(annotation) = annotation {
+            let expected = self.annotation_type(annotation);
+            if !self.unify(&expected, &value) {
+                let reason = format!(
+                    "{} must be a {}, not a {}!",
+                    check::def_name(call).unwrap_or("the value of def"),
+                    self.resolve(&expected),
+                    self.resolve(&value)
+                );
+                self.error(&expected, &value, Some(1), reason);
+            }
+        }
+        if let Some(name) = check::def_name(call) {
+            if let Some(pending) = self.pending.remove(name) {
+                self.unify(&pending, &value);
+            }
+            self.scopes[0].remove(name);
+            let scheme = self.generalize(&value);
+            self.scopes[0].insert(name.to_string(), scheme);
+        }
+        value
+    }
+
+    // The branches of an `if` are functions, and it has the type of whichever is
+    // called.
+    fn infer_if(&mut self, call: &FunctionExpression, pipe: &Type) -> Type {
+        let mut arguments = call.arguments.iter();
+        if let Some(condition) = arguments.next() {
+            let ty = self.infer(&**condition, pipe);
+            if !self.unify(&Type::Boolean, &ty) {
+                let reason = format!(
+                    "argument 1 of if must be a boolean, not a {}!",
+                    self.resolve(&ty)
+                );
+                self.error(&Type::Boolean, &ty, Some(0), reason);
+            }
+        }
+        let mut results = Vec::new();
+        for (position, branch) in arguments.enumerate() {
+            let ty = self.infer(&**branch, pipe);
+            match self.shallow(&ty) {
+                Type::Function(_, result) => results.push(*result),
+                Type::Var(_) | Type::Any => results.push(Type::Any),
+                // null stands for a branch that is left out
+                Type::Null => (),
+                other => self.error(
+                    &function(Vec::new(), Type::Any),
+                    &other,
+                    Some(position + 1),
+                    format!(
+                        "argument {} of if must be a function, not a {}!",
+                        position + 2,
+                        other
+                    ),
+                ),
+            }
+        }
+        self.join(&results)
+    }
+
+    // The operands of and and or are booleans, or blocks returning them.
+    fn infer_logical(&mut self, name: &str, call: &FunctionExpression, pipe: &Type) -> Type {
+        for (position, operand) in call.arguments.iter().enumerate() {
+            let ty = self.infer(&**operand, pipe);
+            let result = match self.shallow(&ty) {
+                Type::Function(_, result) => *result,
+                other => other,
+            };
+            if !self.unify(&Type::Boolean, &result) {
+                let reason = format!(
+                    "argument {} of {} must be a boolean, not a {}!",
+                    position + 1,
+                    name,
+                    self.resolve(&result)
+                );
+                self.error(&Type::Boolean, &result, Some(position), reason);
+            }
+        }
+        Type::Boolean
+    }
+
+    fn infer_match(&mut self, matcher: &MatchExpression, pipe: &Type) -> Type {
+        let subject = self.infer(&*matcher.subject, pipe);
+        let mut results = Vec::new();
+        for arm in &matcher.arms {
+            let mut scope = HashMap::new();
+            self.bind_pattern(&arm.pattern, &subject, &mut scope);
+            self.scopes.push(scope);
+            if let Some(guard) = &arm.guard {
+                let ty = self.infer(&**guard, pipe);
+                if !self.unify(&Type::Boolean, &ty) {
+                    let reason = format!(
+                        "match guards must be booleans, not a {}!",
+                        self.resolve(&ty)
+                    );
+                    self.error(&Type::Boolean, &ty, None, reason);
+                }
+            }
+            results.push(self.infer(&*arm.body, pipe));
+            self.scopes.pop();
+        }
+        self.join(&results)
+    }
+
+    // Patterns don't constrain the value matched, as the arms of a match may
+    // expect different types; names bound inside arrays get the element type if
+    // it is known, and any otherwise.
+    fn bind_pattern(&self, pattern: &Pattern, ty: &Type, scope: &mut HashMap<String, Scheme>) {
+        match pattern {
+            Pattern::Bind(name) => {
+                scope.insert(name.clone(), Scheme::monomorphic(ty.clone()));
+            }
+            Pattern::Array(elements, rest) => {
+                let element = match self.shallow(ty) {
+                    Type::Array(element) => *element,
+                    _ => Type::Any,
+                };
+                for pattern in elements {
+                    self.bind_pattern(pattern, &element, scope);
+                }
+                if let Some(rest) = rest.as_ref().filter(|rest| *rest != "_") {
+                    let rest_type = Type::Array(Box::new(element));
+                    scope.insert(rest.clone(), Scheme::monomorphic(rest_type));
+                }
+            }
+            Pattern::Variant(_, patterns) => {
+                for pattern in patterns {
+                    self.bind_pattern(pattern, &Type::Any, scope);
+                }
+            }
+            Pattern::Record(fields) => {
+                for (_, pattern) in fields {
+                    self.bind_pattern(pattern, &Type::Any, scope);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => (),
+        }
+    }
+}
+
+fn replace_vars(ty: &Type, vars: &HashMap<usize, Type>) -> Type {
+    match ty {
+        Type::Var(var) => vars.get(var).cloned().unwrap_or_else(|| ty.clone()),
+        Type::Array(element) => Type::Array(Box::new(replace_vars(element, vars))),
+        Type::Map(value) => Type::Map(Box::new(replace_vars(value, vars))),
+        Type::Function(parameters, result) => function(
+            parameters.iter().map(|p| replace_vars(p, vars)).collect(),
+            replace_vars(result, vars),
+        ),
+        other => other.clone(),
+    }
+}
+
+// The types of the builtins, which may use the type variable `a`. Builtins
+// that take many kinds of values take any.
+fn builtin_type(name: &str, a: &Type) -> Option<Type> {
+    use Type::*;
+    let array = |element: Type| Array(Box::new(element));
+    let map = |value: Type| Map(Box::new(value));
+    let a = || a.clone();
+    Some(match name {
+        "true" | "false" => Boolean,
+        "null" => Null,
+        "args" => array(String),
+        "print" => function(vec![Any], Null),
+        "def" => function(vec![Any, a(), String], a()),
+        "if" => function(vec![Boolean, Any, Any], Any),
+        "and" | "or" => function(vec![Any, Any], Boolean),
+        "not" => function(vec![Boolean], Boolean),
+        "xor" => function(vec![Boolean, Boolean], Boolean),
+        "+" | "*" => function(vec![Any, Any], Any),
+        "pi" | "e" => Number,
+        "-" | "min" | "max" => function(vec![Number, Number], Number),
+        "abs" | "floor" | "ceil" | "round" | "exp" => function(vec![Number], Number),
+        "sin" | "cos" | "tan" | "atan" => function(vec![Number], Number),
+        // null outside their domains: dividing by zero, the square root of a
+        // negative number and so on
+        "div" | "**" | "idiv" | "mod" | "log" | "atan2" => function(vec![Number, Number], Any),
+        "sqrt" | "asin" | "acos" => function(vec![Number], Any),
+        "=" | "same" => function(vec![Any, Any], Boolean),
+        // any two values can be compared
+        "<" | ">" | "<=" | ">=" => function(vec![Any, Any], Boolean),
+        "compare" | "compareFolded" => function(vec![Any, Any], Number),
+        "type" => function(vec![Any], String),
+        "length" => function(vec![Any], Number),
+        "for" => function(vec![array(Any), function(vec![Number, Any], Any)], Any),
+        "foreach" => function(vec![array(a()), function(vec![a(), Any], Any)], Any),
+        "exit" => function(vec![Number], Any),
+        "assert" => function(vec![Boolean, Any], Boolean),
+        "findIndex" => function(vec![array(a()), a()], Any),
+        "int" | "float" => function(vec![Number], Number),
+        "rational" | "decimal" => function(vec![Any, Number], Number),
+        "string" => function(vec![Any], String),
+        "get" => function(vec![map(Any), Any], Any),
+        "contains" => function(vec![map(Any), Any], Boolean),
+        "insert" => function(vec![map(Any), Any, Any], map(Any)),
+        "remove" => function(vec![map(a()), Any], map(a())),
+        "keys" => function(vec![map(Any)], array(Any)),
+        "values" => function(vec![map(a())], array(a())),
+        "entries" => function(vec![map(Any)], array(array(Any))),
+        "with" => function(vec![Record, String, Any], Record),
+        "fields" => function(vec![Record], array(String)),
+        "variant" => function(vec![String, Number], Any),
+        "tag" => function(vec![Variant], String),
+        "input" => function(vec![], Any),
+        "readFile" => function(vec![String], String),
+        "writeFile" => function(vec![String, String], Null),
+        "fileExists" => function(vec![String], Boolean),
+        "env" => function(vec![String], Any),
+        "now" | "random" => function(vec![], Number),
+        "exec" => function(vec![String, array(String)], String),
+        _ => return None,
+    })
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+
+    fn type_errors(program: &str) -> Vec<String> {
+        let program = crate::parse(program).unwrap();
+        program
+            .check_types()
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn infers_types() {
+        let mut builtins = Scope::new(None);
+        let capabilities = crate::Capabilities::all();
+        crate::stdlib::insert_stdlib(&mut builtins, &Default::default(), capabilities);
+        let unknown: Vec<String> = builtins
+            .names()
+            .into_iter()
+            .filter(|name| super::builtin_type(name, &super::Type::Any).is_none())
+            .collect();
+        assert!(unknown.is_empty(), "builtins without types: {:?}", unknown);
+
+        assert!(type_errors(
+            "(def .'id' /x .x) (div (id 1) 2) (+ (id 'a') 'b')
+             (def .'sum' /list .(foreach list /item total .(+ item total)))
+             (sum [1 2 null 'mixed']) (if (= 1 1) .1 .'one') (print (sum [1]))"
+        )
+        .is_empty());
+        assert_eq!(
+            type_errors("(def .'half' /x .(div x 2))\n(half 'ten')"),
+            ["TypeError: argument 1 of half must be a number, not a string! (at 2:1)"]
+        );
+        assert_eq!(
+            type_errors("(def .'n' (length 'abc')) (n 1) (if 'yes' .1 .2)").len(),
+            2
+        );
+        assert_eq!(
+            type_errors("(and (= 1 1) .(not false)) (or true .'yes')"),
+            ["TypeError: argument 2 of or must be a boolean, not a string! (at 1:28)"]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::ast::parse;
+use crate::capability::Capabilities;
+use crate::common::*;
+use crate::convert::IntoOak;
+use crate::host::{HostFn, HostFunction};
+use crate::output::Output;
+use crate::stdlib;
+use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+// An Oak session: a global scope holding the builtins, the prelude and anything
+// the evaluated programs define, which persists from one call of eval to the
+// next. Values in the global scope can be read and set from Rust, and Oak
+// functions called with Rust-side arguments, so a script can be loaded once and
+// its functions used as callbacks.
+pub struct Interpreter {
+    scope: Rc<Scope>,
+    // result of the last evaluation, available to the next one as `^`
+    last: Rc<Value>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::with_output(Output::default())
+    }
+
+    // Creates an interpreter whose `print` writes to the given output.
+    pub fn with_output(output: Output) -> Self {
+        Interpreter::with_options(output, Capabilities::default())
+    }
+
+    // Creates an interpreter with only the builtins allowed by the given
+    // capabilities; see Capability.
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        Interpreter::with_options(Output::default(), capabilities)
+    }
+
+    pub fn with_options(output: Output, capabilities: Capabilities) -> Self {
+        let mut scope = Scope::new(None);
+        stdlib::insert_stdlib(&mut scope, &output, capabilities);
+        scope.set("args".to_string(), Rc::new(Value::Array(Vec::new())));
+
+        let scope = Rc::new(scope);
+        for expr in stdlib::get_prelude() {
+            expr.evaluate(Rc::clone(&scope), Rc::new(Value::Null))
+                .expect("the prelude should evaluate without errors");
+        }
+
+        Interpreter {
+            scope,
+            last: Rc::new(Value::Null),
+        }
+    }
+
+    // Evaluates a program in the global scope, returning the value of its last
+    // expression. Definitions made at the top level of the program remain
+    // available to later calls.
+    pub fn eval(&mut self, program: &str) -> Result<Rc<Value>, EvalError> {
+        let val = self.eval_aside(program)?;
+        self.last = Rc::clone(&val);
+        Ok(val)
+    }
+
+    // Like eval, but the value doesn't become the next `^` (its side effects and
+    // definitions still happen).
+    pub fn eval_aside(&self, program: &str) -> Result<Rc<Value>, EvalError> {
+        let program = parse(program)?;
+
+        let mut val = Rc::clone(&self.last);
+        for expr in program.expressions.iter() {
+            val = expr.evaluate(Rc::clone(&self.scope), val)?;
+        }
+        Ok(val)
+    }
+
+    // Looks up a global, returning None if it is not defined.
+    pub fn get(&self, name: &str) -> Option<Rc<Value>> {
+        self.scope.lookup(name)
+    }
+
+    // Defines (or replaces) a global, converting it with IntoOak.
+    pub fn set<T: IntoOak>(&self, name: &str, value: T) {
+        self.scope.set(name.to_string(), value.into_oak());
+    }
+
+    // Defines a global function implemented in Rust; see HostFunction and the
+    // host_fn! macro.
+    pub fn register(&self, name: &str, function: HostFunction) {
+        self.set(name, function.named(name).into_value());
+    }
+
+    // Defines a global function from a Rust closure with typed arguments, which
+    // are converted from Oak values with FromOak. For example:
+    //
+    //     interpreter.register_fn("greet", |name: String, times: f64| {
+    //         format!("Hello, {}!", name).repeat(times as usize)
+    //     });
+    //
+    // Closures can capture host state (with a RefCell to mutate it).
+    pub fn register_fn<Args, F: HostFn<Args>>(&self, name: &str, function: F) {
+        self.register(name, HostFunction::from_fn(function));
+    }
+
+    // A flag which, when set (from any thread, or a signal handler), stops the
+    // evaluation in progress at its next function call with an Interrupted
+    // error. The flag is cleared when the error is raised.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.scope.interrupt_flag()
+    }
+
+    // Names defined in the global scope, in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        self.scope.names()
+    }
+
+    // Calls the global function with the given name. Arguments can be values from
+    // eval or get, or Rust values converted with into_oak.
+    pub fn call(&self, name: &str, arguments: Vec<Rc<Value>>) -> Result<Rc<Value>, EvalError> {
+        match self.get(name) {
+            Some(function) => self.apply(&function, arguments),
+            None => Err(EvalError::unbound(name)),
+        }
+    }
+
+    // Calls an Oak function value (or array), such as one returned by eval or get.
+    pub fn apply(
+        &self,
+        function: &Value,
+        arguments: Vec<Rc<Value>>,
+    ) -> Result<Rc<Value>, EvalError> {
+        function.call(arguments)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use super::Interpreter;
+    use crate::common::*;
+    use crate::convert::IntoOak;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn keeps_globals_between_evaluations() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("(def .'double' /x .(* x 2))").unwrap();
+        interpreter.set("base", Value::Number(20.0));
+
+        let result = interpreter.eval("(double base)").unwrap();
+        assert_eq!(*result, Value::Number(40.0));
+        assert_eq!(*interpreter.eval("(+ ^ 2)").unwrap(), Value::Number(42.0));
+        let result = interpreter.eval_aside("(+ ^ 1)").unwrap();
+        assert_eq!(*result, Value::Number(43.0));
+        assert_eq!(*interpreter.eval("^").unwrap(), Value::Number(42.0));
+
+        let result = interpreter.call("double", vec![4.0.into_oak()]).unwrap();
+        assert_eq!(*result, Value::Number(8.0));
+        // results can be passed back in
+        let double = interpreter.get("double").unwrap();
+        let result = interpreter.apply(&double, vec![result]).unwrap();
+        assert_eq!(*result, Value::Number(16.0));
+        assert!(interpreter.get("missing").is_none());
+    }
+
+    #[test]
+    fn stops_when_interrupted() {
+        let mut interpreter = Interpreter::new();
+        let flag = interpreter.interrupt_flag();
+        interpreter.register_fn("stop", move || flag.store(true, Ordering::Relaxed));
+        interpreter.eval("(def .'count' /n .(+ n 1))").unwrap();
+
+        let err = interpreter.eval("(count 0) (stop) (count 1)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Interrupted);
+        // the flag was cleared, so the interpreter can go on
+        assert_eq!(*interpreter.eval("(count 2)").unwrap(), Value::Number(3.0));
+    }
+}
@@ -0,0 +1,390 @@
+use crate::capability::{Capabilities, Capability};
+use crate::common::*;
+use crate::convert::IntoOak;
+use crate::host::HostFunction;
+use crate::interpreter::Interpreter;
+use crate::number::Number;
+use crate::output::Output;
+use crate::Config;
+use js_sys::{Array, Function as JsFunction, Object, Reflect};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// The largest integer a JS number holds exactly (Number.MAX_SAFE_INTEGER)
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+// expect logging function (log_oak) to be exposed globally in the JS, unless an
+// output callback is given
+#[wasm_bindgen]
+extern "C" {
+    pub(crate) fn log_oak(s: &str);
+}
+
+// Programs run in the browser are untrusted, so by default they only get the
+// capabilities that don't reach outside the page.
+fn browser_capabilities() -> Capabilities {
+    Capabilities::pure()
+        .with(Capability::Console)
+        .with(Capability::Exit)
+        .with(Capability::Clock)
+        .with(Capability::Random)
+}
+
+fn callback_output(callback: JsFunction) -> Output {
+    Output::callback(move |line| {
+        callback
+            .call1(&JsValue::NULL, &JsValue::from_str(line))
+            .ok();
+    })
+}
+
+// Runs a program, returning its result or error as a string. Printed lines are
+// passed to the output callback if one is given, or else to a global log_oak
+// function.
+#[wasm_bindgen]
+pub fn run_oak(program: String, output: Option<JsFunction>) -> JsValue {
+    let mut config = Config::new(program).with_capabilities(browser_capabilities());
+    if let Some(callback) = output {
+        config = config.with_output(callback_output(callback));
+    }
+    match config.run() {
+        Ok(val) => JsValue::from_str(&val.to_string()),
+        Err(e) => match e.exit_code() {
+            Some(code) => JsValue::from_str(&format!("Oak exited with code {}", code)),
+            None => JsValue::from_str(&e.to_string()),
+        },
+    }
+}
+
+// An Oak session for JavaScript, wrapping Interpreter. Values are converted
+// between Oak and JS: numbers, strings, booleans and null map to themselves,
+// arrays to arrays, records and maps with string keys to plain objects (other
+// maps to JS Maps), variants to `{tag, payload}` objects and functions to
+// callable JS functions (and back). Oak
+// errors are thrown as JS Errors with `kind`, `reason`, `line`, `column` and
+// `exitCode` properties.
+#[wasm_bindgen(js_name = Interpreter)]
+pub struct JsInterpreter {
+    interpreter: Interpreter,
+}
+
+#[wasm_bindgen(js_class = Interpreter)]
+impl JsInterpreter {
+    // `output` is called with each printed line (log_oak is used without it), and
+    // `capabilities` is an array of capability names to grant instead of the
+    // browser defaults.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        output: Option<JsFunction>,
+        capabilities: Option<Array>,
+    ) -> Result<JsInterpreter, JsValue> {
+        let capabilities = match capabilities {
+            Some(names) => names.iter().try_fold(Capabilities::pure(), |caps, name| {
+                let name = name.as_string().unwrap_or_default();
+                match Capability::from_name(&name) {
+                    Some(capability) => Ok(caps.with(capability)),
+                    None => Err(js_error(&EvalError::host(format!(
+                        "unknown capability {}",
+                        name
+                    )))),
+                }
+            })?,
+            None => browser_capabilities(),
+        };
+        let output = output.map(callback_output).unwrap_or_default();
+
+        Ok(JsInterpreter {
+            interpreter: Interpreter::with_options(output, capabilities),
+        })
+    }
+
+    // Evaluates a program, returning the value of its last expression.
+    pub fn eval(&mut self, program: &str) -> Result<JsValue, JsValue> {
+        let val = self.interpreter.eval(program).map_err(|e| js_error(&e))?;
+        to_js(&val).map_err(|e| js_error(&e))
+    }
+
+    // Calls the global Oak function with the given name.
+    pub fn call(&self, name: &str, args: Array) -> Result<JsValue, JsValue> {
+        let function = self
+            .interpreter
+            .get(name)
+            .ok_or_else(|| js_error(&EvalError::unbound(name)))?;
+        call_oak(&function, &args).map_err(|e| js_error(&e))
+    }
+
+    // Looks up a global, returning undefined if it is not defined.
+    pub fn get(&self, name: &str) -> Result<JsValue, JsValue> {
+        match self.interpreter.get(name) {
+            Some(val) => to_js(&val).map_err(|e| js_error(&e)),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    pub fn set(&self, name: &str, value: JsValue) -> Result<(), JsValue> {
+        let value = from_js(&value).map_err(|e| js_error(&e))?;
+        self.interpreter.set(name, value);
+        Ok(())
+    }
+
+    // Defines a global Oak function that calls the JS function. Its arity is the
+    // function's `length`, unless given.
+    #[wasm_bindgen(js_name = registerFunction)]
+    pub fn register_function(&self, name: &str, function: JsFunction, arity: Option<u32>) {
+        let arity = arity.unwrap_or_else(|| function.length());
+        self.interpreter
+            .register(name, js_host_function(function, arity as usize));
+    }
+}
+
+fn js_host_function(function: JsFunction, arity: usize) -> HostFunction {
+    let parameters: Vec<String> = (0..arity).map(|i| format!("arg{}", i)).collect();
+    let parameters: Vec<&str> = parameters.iter().map(String::as_str).collect();
+    HostFunction::new(&parameters, move |args| {
+        let js_args = Array::new();
+        for position in 0..args.len() {
+            js_args.push(&to_js(&args.get::<Rc<Value>>(position)?)?);
+        }
+        let result = function
+            .apply(&JsValue::NULL, &js_args)
+            .map_err(|err| EvalError::host(describe_js_error(&err)))?;
+        from_js(&result)
+    })
+}
+
+fn call_oak(function: &Value, args: &Array) -> Result<JsValue, EvalError> {
+    let args = args
+        .iter()
+        .map(|arg| from_js(&arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    to_js(&function.call(args)?)
+}
+
+// Converts an Oak value to JS.
+pub fn to_js(value: &Rc<Value>) -> Result<JsValue, EvalError> {
+    Ok(match &**value {
+        Value::Number(num) => JsValue::from_f64(*num),
+        // JS has no exact fractions, so these become the nearest number
+        Value::Rational(_) | Value::Decimal(_) => {
+            JsValue::from_f64(Number::from_value(value).expect("a number").to_f64())
+        }
+        // integers beyond the range JS numbers hold exactly become BigInts
+        Value::Integer(int) => match int.to_i64().filter(|int| int.abs() <= MAX_SAFE_INTEGER) {
+            Some(int) => JsValue::from_f64(int as f64),
+            None => js_sys::BigInt::new(&JsValue::from_str(&int.to_string()))
+                .map_err(|err| EvalError::host(describe_js_error(&err)))?
+                .into(),
+        },
+        Value::StringType(st) => JsValue::from_str(st),
+        Value::Boolean(b) => JsValue::from_bool(*b),
+        Value::Null => JsValue::NULL,
+        Value::Array(elements) => {
+            let array = Array::new();
+            for element in elements {
+                array.push(&to_js(element)?);
+            }
+            array.into()
+        }
+        Value::Record(record) => {
+            let object = Object::new();
+            for (name, field) in record.fields() {
+                Reflect::set(&object, &JsValue::from_str(name), &to_js(field)?).ok();
+            }
+            object.into()
+        }
+        // variants become `{tag, payload}` objects
+        Value::Variant(variant) => {
+            let object = Object::new();
+            let payload = Array::new();
+            for element in variant.payload() {
+                payload.push(&to_js(element)?);
+            }
+            Reflect::set(
+                &object,
+                &JsValue::from_str("tag"),
+                &JsValue::from_str(variant.tag()),
+            )
+            .ok();
+            Reflect::set(&object, &JsValue::from_str("payload"), &payload).ok();
+            object.into()
+        }
+        Value::Map(map)
+            if map
+                .keys()
+                .all(|key| matches!(key.value(), Value::StringType(_))) =>
+        {
+            let object = Object::new();
+            for (key, entry) in sorted_entries(map) {
+                Reflect::set(&object, &to_js(&key.to_value())?, &to_js(entry)?).ok();
+            }
+            object.into()
+        }
+        Value::Map(map) => {
+            let js_map = js_sys::Map::new();
+            for (key, entry) in sorted_entries(map) {
+                js_map.set(&to_js(&key.to_value())?, &to_js(entry)?);
+            }
+            js_map.into()
+        }
+        Value::Function(_) => {
+            // JS functions are variadic, so the closure takes the arguments as an
+            // array and a wrapper spreads them.
+            let function = Rc::clone(value);
+            let closure = Closure::wrap(Box::new(move |args: Array| {
+                call_oak(&function, &args).map_err(|e| js_error(&e))
+            })
+                as Box<dyn Fn(Array) -> Result<JsValue, JsValue>>);
+            let wrapper = JsFunction::new_with_args("f", "return (...args) => f(args);");
+            // The closure is freed when the JS function is garbage collected.
+            wrapper
+                .call1(&JsValue::NULL, &closure.into_js_value())
+                .expect("creating the wrapper should not throw")
+        }
+    })
+}
+
+// Converts a JS value to Oak.
+pub fn from_js(value: &JsValue) -> Result<Rc<Value>, EvalError> {
+    if value.is_null() || value.is_undefined() {
+        Ok(Rc::new(Value::Null))
+    } else if let Some(num) = value.as_f64() {
+        // JS has no separate integer type, so integral numbers become integers
+        match Number::Float(num)
+            .to_integer()
+            .filter(|_| num.abs() <= MAX_SAFE_INTEGER as f64)
+        {
+            Some(int) => Ok(int.into_oak()),
+            None => Ok(num.into_oak()),
+        }
+    } else if let Some(int) = value.dyn_ref::<js_sys::BigInt>() {
+        let digits = String::from(
+            int.to_string(10)
+                .map_err(|err| EvalError::host(describe_js_error(&err)))?,
+        );
+        Ok(digits
+            .parse::<BigInt>()
+            .expect("BigInts are written as integers")
+            .into_oak())
+    } else if let Some(st) = value.as_string() {
+        Ok(st.into_oak())
+    } else if let Some(b) = value.as_bool() {
+        Ok(b.into_oak())
+    } else if Array::is_array(value) {
+        let elements = Array::from(value)
+            .iter()
+            .map(|element| from_js(&element))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(elements.into_oak())
+    } else if let Some(function) = value.dyn_ref::<JsFunction>() {
+        let arity = function.length() as usize;
+        Ok(js_host_function(function.clone(), arity)
+            .into_value()
+            .into_oak())
+    } else if let Some(js_map) = value.dyn_ref::<js_sys::Map>() {
+        let mut entries = Vec::new();
+        js_map.for_each(&mut |entry, key| entries.push((key, entry)));
+        let mut map = HashMap::new();
+        for (key, entry) in entries {
+            map.insert(Key::new(from_js(&key)?), from_js(&entry)?);
+        }
+        Ok(Rc::new(Value::Map(map)))
+    } else if value.is_object() {
+        let mut map = HashMap::new();
+        for entry in Object::entries(value.unchecked_ref()).iter() {
+            let entry = Array::from(&entry);
+            let name = entry.get(0).as_string().unwrap_or_default();
+            map.insert(Key::new(name.into_oak()), from_js(&entry.get(1))?);
+        }
+        Ok(Rc::new(Value::Map(map)))
+    } else {
+        Err(EvalError::host(format!(
+            "{} cannot be converted to an Oak value",
+            describe_js_error(value)
+        )))
+    }
+}
+
+fn describe_js_error(err: &JsValue) -> String {
+    match err.dyn_ref::<js_sys::Error>() {
+        Some(error) => String::from(error.message()),
+        None => err.as_string().unwrap_or_else(|| format!("{:?}", err)),
+    }
+}
+
+// Converts an Oak error to a JS Error, with the parts of the error as properties.
+fn js_error(error: &EvalError) -> JsValue {
+    let js_error = js_sys::Error::new(&error.to_string());
+    js_error.set_name("OakError");
+    let set = |key: &str, value: JsValue| {
+        Reflect::set(&js_error, &JsValue::from_str(key), &value).ok();
+    };
+    set("kind", JsValue::from_str(&error.kind.to_string()));
+    set("reason", JsValue::from_str(&error.reason));
+    match error.location {
+        Some(location) => {
+            set("line", JsValue::from_f64(location.line as f64));
+            set("column", JsValue::from_f64(location.column as f64));
+        }
+        None => {
+            set("line", JsValue::NULL);
+            set("column", JsValue::NULL);
+        }
+    }
+    match error.exit_code() {
+        Some(code) => set("exitCode", JsValue::from_f64(code as f64)),
+        None => set("exitCode", JsValue::NULL),
+    }
+    js_error.into()
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+// Run with `wasm-pack test --node`, or cargo test with wasm-bindgen-test-runner
+// as the wasm32 runner.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn property(object: &JsValue, key: &str) -> JsValue {
+        Reflect::get(object, &JsValue::from_str(key)).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn converts_between_oak_and_js() {
+        let mut interpreter = JsInterpreter::new(None, None).unwrap();
+        let add = JsFunction::new_with_args("a, b", "return a + b");
+        interpreter.register_function("add", add, None);
+
+        let result = interpreter
+            .eval("[(add 1 2) 'oak' null #(x true) #{'k' 1}]")
+            .unwrap();
+        let result = Array::from(&result);
+        assert_eq!(result.get(0).as_f64(), Some(3.0));
+        assert_eq!(result.get(1).as_string().as_deref(), Some("oak"));
+        assert!(result.get(2).is_null());
+        assert_eq!(property(&result.get(3), "x").as_bool(), Some(true));
+        assert_eq!(property(&result.get(4), "k").as_f64(), Some(1.0));
+
+        // Oak functions become callable JS functions
+        let double = interpreter.eval("/x .(* x 2)").unwrap();
+        let double: JsFunction = double.unchecked_into();
+        let doubled = double.call1(&JsValue::NULL, &JsValue::from_f64(21.0));
+        assert_eq!(doubled.unwrap().as_f64(), Some(42.0));
+
+        // errors are thrown with their kind and location
+        let err = interpreter.eval("\n(+ 1 'a')").unwrap_err();
+        assert_eq!(
+            property(&err, "kind").as_string().as_deref(),
+            Some("TypeError")
+        );
+        assert_eq!(property(&err, "line").as_f64(), Some(2.0));
+        let err = interpreter.eval("(exit 4)").unwrap_err();
+        assert_eq!(property(&err, "exitCode").as_f64(), Some(4.0));
+    }
+}
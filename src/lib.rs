@@ -1,53 +1,130 @@
 use std::rc::Rc;
 
-#[cfg(target_arch = "wasm32")]
-use wasm_bindgen::prelude::*;
-
+mod ast;
+mod capability;
+mod check;
 mod common;
+mod convert;
+mod decimal;
 mod expressions;
+#[cfg(not(target_arch = "wasm32"))]
+mod ffi;
+mod host;
+mod infer;
+mod interpreter;
+#[cfg(target_arch = "wasm32")]
+mod js;
+mod number;
+mod output;
+mod record;
 mod stdlib;
-mod util;
+mod variant;
 
-use expressions::Expression;
+pub use ast::{parse, Program};
+pub use capability::{Capabilities, Capability};
+pub use common::{ErrorKind, EvalError, Function, Location, Value};
+pub use convert::{FromOak, IntoOak};
+pub use host::{Arguments, HostFn, HostFunction, IntoOakResult};
+pub use interpreter::Interpreter;
+pub use num_bigint::BigInt;
+pub use number::Number;
+pub use output::Output;
+pub use record::Record;
+pub use variant::Variant;
 
 pub struct Config {
     pub program: String,
+    // Command-line arguments, made available to the program as the `args` array
+    pub args: Vec<String>,
+    // Where `print` writes; standard output by default
+    pub output: Output,
+    // Which builtins the program may use; the pure, console and exit ones by default
+    pub capabilities: Capabilities,
 }
 
 impl Config {
     pub fn new(program: String) -> Self {
-        Config { program }
+        Config {
+            program,
+            args: Vec::new(),
+            output: Output::default(),
+            capabilities: Capabilities::default(),
+        }
     }
 
-    pub fn run(&self) -> Result<Rc<common::Value>, common::EvalError> {
-        let mut str_iter = common::StringIterator::new(&self.program);
-
-        // create a block expression that contains all the expressions in the prelude,
-        // plus another block expression containing the file contents
-        let mut expressions = stdlib::get_prelude();
-        expressions.push(Rc::new(expressions::BlockExpression::new(&mut str_iter)?));
-        let main_expression = crate::expressions::BlockExpression { expressions };
-        let mut prgm_scope = common::Scope::new(None);
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
 
-        // insert stdlib
-        stdlib::insert_stdlib(&mut prgm_scope);
+    pub fn with_output(mut self, output: Output) -> Self {
+        self.output = output;
+        self
+    }
 
-        main_expression.evaluate(Rc::new(prgm_scope), Rc::new(common::Value::Null))
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
     }
-}
 
-#[cfg(target_arch = "wasm32")]
-#[wasm_bindgen]
-pub fn run_oak(program: String) -> JsValue {
-    match Config::new(program).run() {
-        Ok(val) => JsValue::from_str(&val.to_string()),
-        Err(e) => JsValue::from_str(&e.to_string()),
+    // Runs the program. Note that a call to `exit` is returned as an EvalError of
+    // kind ErrorKind::Exit, carrying the exit code; see EvalError::exit_code.
+    pub fn run(&self) -> Result<Rc<Value>, EvalError> {
+        let mut interpreter = Interpreter::with_options(self.output.clone(), self.capabilities);
+        interpreter.set("args", self.args.clone());
+
+        interpreter.eval(&self.program)
     }
 }
 
-// expect logging function (log_oak) to be exposed globally in the JS
-#[cfg(target_arch = "wasm32")]
-#[wasm_bindgen]
-extern "C" {
-    fn log_oak(s: &str);
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use super::{Config, Interpreter, Output};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn exits_by_unwinding() {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let program = "(print 'before')
+            (foreach [1 2 3] /x prev .{ (if (= x 2) .{ (exit 7) } .null) (print x) })
+            (print 'after')";
+        let err = Config::new(program.to_string())
+            .with_output(Output::shared(Rc::clone(&buffer)))
+            .run()
+            .unwrap_err();
+        // the exit code comes back to the host, and nothing after exit ran
+        assert_eq!(err.exit_code(), Some(7));
+        assert_eq!(
+            String::from_utf8(buffer.borrow().clone()).unwrap(),
+            "\"before\"\n1\n"
+        );
+
+        // nor do the arguments after exit, or after an error
+        buffer.borrow_mut().clear();
+        let program = "(print (exit 3) (print 'after exit'))";
+        let err = Config::new(program.to_string())
+            .with_output(Output::shared(Rc::clone(&buffer)))
+            .run()
+            .unwrap_err();
+        assert_eq!(err.exit_code(), Some(3));
+        let program = "(+ (undefinedthing 1) (print 'still ran'))";
+        let err = Config::new(program.to_string())
+            .with_output(Output::shared(Rc::clone(&buffer)))
+            .run()
+            .unwrap_err();
+        assert_eq!(err.exit_code(), None);
+        assert!(buffer.borrow().is_empty());
+
+        // an interpreter is still usable after its program exits
+        let mut interpreter = Interpreter::new();
+        let err = interpreter
+            .eval("(def .'x' 1) (exit) (def .'x' 2)")
+            .unwrap_err();
+        assert_eq!(err.exit_code(), Some(0));
+        assert_eq!(interpreter.eval("(+ x 1)").unwrap().to_string(), "2");
+    }
 }
@@ -1,28 +1,24 @@
 use std::env;
-use std::fs;
-use std::process;
 
-use oak::Config;
+mod cli;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    if args.len() < 2 {
-        eprintln!("Oak: missing filename!");
-        process::exit(1);
-    }
-
-    let filename = args[1].clone();
-
-    let program: String = fs::read_to_string(&filename).unwrap_or_else(|_err| {
-        eprintln!("Oak: file not found!");
-        process::exit(1);
-    });
-
-    let prgm_config = Config::new(program);
-
-    match prgm_config.run() {
-        Ok(val) => println!("Oak - result: {}", &*val),
-        Err(e) => eprintln!("Oak - interpreter error: {}", e.reason),
+    // Without a command, the arguments are those of `run`
+    let (command, rest) = match args.first() {
+        Some(command) => (command.as_str(), &args[1..]),
+        None => cli::usage_error("missing filename!"),
     };
+
+    match command {
+        "run" => cli::run::main(rest),
+        "check" => cli::tools::check(rest),
+        "fmt" => cli::tools::fmt(rest),
+        "ast" => cli::tools::ast(rest),
+        "test" => cli::test::main(rest),
+        "repl" => cli::repl::main(rest),
+        "help" | "--help" | "-h" => println!("{}", cli::USAGE),
+        _ => cli::run::main(&args),
+    }
 }
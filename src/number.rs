@@ -0,0 +1,612 @@
+// Numbers. Oak has exact integers, which grow as large as they need to,
+// rationals (1/3), fixed-point decimals (19.99d) and floating point numbers. A
+// numeric literal without a decimal point is an integer, and one with a point is
+// a float unless it has a `d` suffix.
+//
+// Arithmetic is exact unless a float is involved, in which case the result is a
+// float. Otherwise mixing integers with decimals gives a decimal, and mixing in
+// a rational gives a rational (or an integer, if it is whole).
+
+use crate::common::*;
+use crate::convert::{conversion_error, FromOak, IntoOak};
+use crate::decimal::{self, Decimal, DIVISION_PLACES};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::str::FromStr;
+
+// Exponents of exact powers are limited, since the result of a larger one would
+// not fit in memory anyway.
+const MAX_EXPONENT: u32 = 1 << 20;
+
+// Exact results are limited to about this many bits (8 MiB), which is checked
+// before computing products and powers, as they are what grows numbers quickly.
+const MAX_BITS: u64 = 1 << 26;
+
+// Fails if a result of the given number of bits would be too large.
+fn check_bits(bits: u64) -> Result<(), EvalError> {
+    if bits > MAX_BITS {
+        return Err(EvalError::new(
+            ErrorKind::Limit,
+            format!(
+                "exact numbers can have at most {} bits, but the result would have up to {}!",
+                MAX_BITS, bits
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Integer(BigInt),
+    Rational(BigRational),
+    Decimal(Decimal),
+    Float(f64),
+}
+
+impl Number {
+    // A rational, or an integer if it is whole.
+    pub fn rational(rational: BigRational) -> Number {
+        if rational.is_integer() {
+            Number::Integer(rational.to_integer())
+        } else {
+            Number::Rational(rational)
+        }
+    }
+
+    pub fn from_value(value: &Value) -> Option<Number> {
+        match value {
+            Value::Integer(int) => Some(Number::Integer(int.clone())),
+            Value::Rational(rational) => Some(Number::Rational(rational.clone())),
+            Value::Decimal(decimal) => Some(Number::Decimal(decimal.clone())),
+            Value::Number(num) => Some(Number::Float(*num)),
+            _ => None,
+        }
+    }
+
+    pub fn into_value(self) -> Value {
+        match self {
+            Number::Integer(int) => Value::Integer(int),
+            Number::Rational(rational) => Value::Rational(rational),
+            Number::Decimal(decimal) => Value::Decimal(decimal),
+            Number::Float(num) => Value::Number(num),
+        }
+    }
+
+    // The nearest float, which is infinite for numbers too large for one.
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Integer(int) => int.to_f64().unwrap_or(f64::NAN),
+            Number::Rational(rational) => rational.to_f64().unwrap_or(f64::NAN),
+            Number::Decimal(decimal) => decimal.to_f64(),
+            Number::Float(num) => *num,
+        }
+    }
+
+    // The exact value, which every number has except NaN and the infinities.
+    pub fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Number::Integer(int) => Some(BigRational::from_integer(int.clone())),
+            Number::Rational(rational) => Some(rational.clone()),
+            Number::Decimal(decimal) => Some(decimal.to_rational()),
+            Number::Float(num) => BigRational::from_float(*num),
+        }
+    }
+
+    // The number as an integer, if it has no fractional part.
+    pub fn to_integer(&self) -> Option<BigInt> {
+        match self {
+            Number::Integer(int) => Some(int.clone()),
+            other => other
+                .to_rational()
+                .filter(|rational| rational.is_integer())
+                .map(|rational| rational.to_integer()),
+        }
+    }
+
+    // Integers and decimals as decimals.
+    fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            Number::Integer(int) => Some(Decimal::from_integer(int.clone())),
+            Number::Decimal(decimal) => Some(decimal.clone()),
+            _ => None,
+        }
+    }
+
+    // Converts any number to a decimal, which is None for NaN and the
+    // infinities. Floats convert by their shortest representation, so 0.1 is
+    // 0.1, and rationals exactly if they terminate, or else rounded like a
+    // quotient of decimals.
+    pub fn convert_to_decimal(&self) -> Option<Decimal> {
+        match self {
+            Number::Float(num) => Decimal::from_f64(*num),
+            Number::Rational(rational) => Some(
+                Decimal::exact(rational)
+                    .unwrap_or_else(|| Decimal::from_rational(rational, DIVISION_PLACES)),
+            ),
+            other => other.to_decimal(),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(int) => int.is_zero(),
+            Number::Rational(rational) => rational.is_zero(),
+            Number::Decimal(decimal) => decimal.is_zero(),
+            Number::Float(num) => *num == 0.0,
+        }
+    }
+
+    pub fn is_nan(&self) -> bool {
+        matches!(self, Number::Float(num) if num.is_nan())
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, Number::Float(_))
+    }
+
+    fn is_rational(&self) -> bool {
+        matches!(self, Number::Rational(_))
+    }
+
+    // Compares the exact values of numbers of any kinds. NaN is unordered.
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => Some(a.cmp(b)),
+            (Number::Float(a), Number::Float(b)) => a.partial_cmp(b),
+            _ => match (self.to_rational(), other.to_rational()) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                // one is a float that is NaN or infinite, and the other exact, so
+                // anything finite will do in its place
+                _ => {
+                    let a = if self.is_float() { self.to_f64() } else { 0.0 };
+                    let b = if other.is_float() {
+                        other.to_f64()
+                    } else {
+                        0.0
+                    };
+                    a.partial_cmp(&b)
+                }
+            },
+        }
+    }
+
+    // Applies an exact operation to numbers of the same kind, after converting
+    // them to the kind of the result; floats are handled by the caller.
+    fn exact<I, R, D>(&self, other: &Number, integer: I, rational: R, decimal: D) -> Number
+    where
+        I: Fn(&BigInt, &BigInt) -> BigInt,
+        R: Fn(BigRational, BigRational) -> BigRational,
+        D: Fn(&Decimal, &Decimal) -> Decimal,
+    {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => Number::Integer(integer(a, b)),
+            _ if self.is_rational() || other.is_rational() => {
+                let (a, b) = (self.to_rational(), other.to_rational());
+                Number::rational(rational(
+                    a.expect("exact numbers are rationals"),
+                    b.expect("exact numbers are rationals"),
+                ))
+            }
+            _ => {
+                let (a, b) = (self.to_decimal(), other.to_decimal());
+                Number::Decimal(decimal(
+                    &a.expect("integers are decimals"),
+                    &b.expect("integers are decimals"),
+                ))
+            }
+        }
+    }
+
+    // The size of an exact number, which for rationals is that of the numerator
+    // and denominator together.
+    fn bits(&self) -> u64 {
+        match self {
+            Number::Integer(int) => int.bits(),
+            Number::Rational(rational) => rational.numer().bits() + rational.denom().bits(),
+            Number::Decimal(decimal) => decimal.bits(),
+            Number::Float(_) => unreachable!("floats are handled by the caller"),
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> Number {
+        if self.is_float() || other.is_float() {
+            return Number::Float(self.to_f64() + other.to_f64());
+        }
+        self.exact(other, |a, b| a + b, |a, b| a + b, Decimal::add)
+    }
+
+    // Fails if the product of decimals would have more than MAX_SCALE places, or
+    // the product would have more than MAX_BITS.
+    pub fn multiply(&self, other: &Number) -> Result<Number, EvalError> {
+        if self.is_float() || other.is_float() {
+            return Ok(Number::Float(self.to_f64() * other.to_f64()));
+        }
+        let places = |num: &Number| match num {
+            Number::Decimal(decimal) => u64::from(decimal.scale()),
+            _ => 0,
+        };
+        decimal::check_scale(places(self) + places(other))?;
+        check_bits(self.bits() + other.bits())?;
+        Ok(self.exact(
+            other,
+            |a, b| a * b,
+            |a, b| a * b,
+            |a, b| a.multiply(b).expect("the scale is checked above"),
+        ))
+    }
+
+    // Division of integers is exact when the divisor divides evenly, and gives a
+    // float otherwise (as does division by zero), so integers never silently
+    // become rationals. Other exact numbers divide exactly, but decimals whose
+    // quotient doesn't terminate are rounded (see Decimal::divide).
+    pub fn divide(&self, other: &Number) -> Result<Number, EvalError> {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) if !b.is_zero() && (a % b).is_zero() => {
+                Ok(Number::Integer(a / b))
+            }
+            (Number::Integer(_), Number::Integer(_)) => {
+                Ok(Number::Float(self.to_f64() / other.to_f64()))
+            }
+            _ if self.is_float() || other.is_float() => {
+                Ok(Number::Float(self.to_f64() / other.to_f64()))
+            }
+            _ if other.is_zero() => Err(EvalError::host(format!(
+                "{} cannot be divided by zero!",
+                self.clone().into_value()
+            ))),
+            _ => Ok(self.exact(
+                other,
+                |a, b| a / b,
+                |a, b| a / b,
+                |a, b| a.divide(b).expect("the divisor is not zero"),
+            )),
+        }
+    }
+
+    // Powers of exact numbers with an integer exponent are exact, except that
+    // integers with a negative exponent give a float (as they do with div).
+    pub fn power(&self, exponent: &Number) -> Result<Number, EvalError> {
+        let exp = match exponent {
+            Number::Integer(exp) if !self.is_float() => exp,
+            _ => return Ok(Number::Float(self.to_f64().powf(exponent.to_f64()))),
+        };
+        if let Number::Integer(base) = self {
+            if exp.is_negative() {
+                return Ok(Number::Float(self.to_f64().powf(exponent.to_f64())));
+            }
+            // 0, 1 and -1 stay small whatever the exponent
+            if base.abs() <= BigInt::one() && exp.to_u32().is_none_or(|e| e > MAX_EXPONENT) {
+                let even = !exp.bit(0);
+                return Ok(Number::Integer(if base.is_negative() && even {
+                    BigInt::one()
+                } else {
+                    base.clone()
+                }));
+            }
+        }
+        let magnitude = match exp.abs().to_u32().filter(|exp| *exp <= MAX_EXPONENT) {
+            Some(magnitude) => magnitude,
+            None => {
+                return Err(EvalError::new(
+                    ErrorKind::Limit,
+                    format!("the exponent {} is too large!", exp),
+                ))
+            }
+        };
+        check_bits(self.bits().saturating_mul(u64::from(magnitude)))?;
+        if exp.is_negative() && self.is_zero() {
+            return Err(EvalError::host(
+                "0 cannot be raised to a negative power!".to_string(),
+            ));
+        }
+        Ok(match self {
+            Number::Integer(base) => Number::Integer(base.pow(magnitude)),
+            Number::Rational(base) => {
+                let power =
+                    BigRational::new(base.numer().pow(magnitude), base.denom().pow(magnitude));
+                if exp.is_negative() {
+                    Number::rational(power.recip())
+                } else {
+                    Number::rational(power)
+                }
+            }
+            Number::Decimal(base) if exp.is_negative() => {
+                let one = Decimal::from_integer(BigInt::one());
+                Number::Decimal(
+                    one.divide(&base.pow(magnitude)?)
+                        .expect("the base is not zero"),
+                )
+            }
+            Number::Decimal(base) => Number::Decimal(base.pow(magnitude)?),
+            Number::Float(_) => unreachable!("floats are handled above"),
+        })
+    }
+
+    // The number with its sign flipped, which is the same kind of number.
+    pub fn negate(&self) -> Number {
+        match self {
+            Number::Integer(int) => Number::Integer(-int),
+            Number::Rational(rational) => Number::Rational(-rational),
+            Number::Decimal(decimal) => Number::Decimal(decimal.negate()),
+            Number::Float(num) => Number::Float(-num),
+        }
+    }
+
+    pub fn subtract(&self, other: &Number) -> Number {
+        self.add(&other.negate())
+    }
+
+    pub fn abs(&self) -> Number {
+        match self {
+            Number::Float(num) => Number::Float(num.abs()),
+            other if other.compare(&Number::Integer(BigInt::zero())) == Some(Ordering::Less) => {
+                other.negate()
+            }
+            other => other.clone(),
+        }
+    }
+
+    // Rounding to a whole number, which is an integer for exact numbers and a
+    // float for floats. round takes ties to the even neighbour, as decimals do.
+    pub fn floor(&self) -> Number {
+        self.whole(f64::floor, BigRational::floor)
+    }
+
+    pub fn ceil(&self) -> Number {
+        self.whole(f64::ceil, BigRational::ceil)
+    }
+
+    pub fn round(&self) -> Number {
+        self.whole(f64::round_ties_even, |rational| {
+            let floor = rational.floor();
+            let twice_fraction = (rational - &floor) * BigInt::from(2);
+            match twice_fraction.cmp(&BigRational::one()) {
+                Ordering::Less => floor,
+                Ordering::Equal if !floor.to_integer().bit(0) => floor,
+                _ => floor + BigInt::one(),
+            }
+        })
+    }
+
+    fn whole(&self, float: fn(f64) -> f64, exact: fn(&BigRational) -> BigRational) -> Number {
+        match self {
+            Number::Float(num) => Number::Float(float(*num)),
+            other => {
+                let rational = other.to_rational().expect("exact numbers are rationals");
+                Number::Integer(exact(&rational).to_integer())
+            }
+        }
+    }
+
+    // The quotient rounded down, which is an integer for exact numbers and a
+    // float for floats. It is None when dividing by zero.
+    pub fn floor_divide(&self, other: &Number) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        if self.is_float() || other.is_float() {
+            return Some(Number::Float((self.to_f64() / other.to_f64()).floor()));
+        }
+        let (a, b) = (self.to_rational()?, other.to_rational()?);
+        Some(Number::Integer((a / b).floor().to_integer()))
+    }
+
+    // The remainder of floor_divide, which has the sign of the divisor and is the
+    // kind of number arithmetic on the two would give.
+    pub fn modulo(&self, other: &Number) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        if self.is_float() || other.is_float() {
+            let (a, b) = (self.to_f64(), other.to_f64());
+            let remainder = a % b;
+            return Some(Number::Float(
+                if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+                    remainder + b
+                } else {
+                    remainder
+                },
+            ));
+        }
+        let quotient = self.floor_divide(other)?;
+        let multiple = other
+            .multiply(&quotient)
+            .expect("multiplying by an integer keeps the scale");
+        Some(self.subtract(&multiple))
+    }
+}
+
+// Hashes the exact value, so that numbers that compare equal hash equally.
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.to_rational() {
+            Some(rational) => rational.hash(state),
+            None => self.to_f64().to_bits().hash(state),
+        }
+    }
+}
+
+// Parses a number written as a literal: 12, -1.5, 19.99d or 1/3.
+impl FromStr for Number {
+    type Err = ();
+
+    fn from_str(st: &str) -> Result<Self, ()> {
+        if let Some(decimal) = st.strip_suffix('d') {
+            decimal.parse().map(Number::Decimal)
+        } else if let Some((numerator, denominator)) = st.split_once('/') {
+            let numerator: BigInt = numerator.parse().map_err(|_| ())?;
+            let denominator: BigInt = denominator.parse().map_err(|_| ())?;
+            if denominator.is_zero() {
+                return Err(());
+            }
+            Ok(Number::rational(BigRational::new(numerator, denominator)))
+        } else if st.contains('.') {
+            st.parse().map(Number::Float).map_err(|_| ())
+        } else {
+            st.parse().map(Number::Integer).map_err(|_| ())
+        }
+    }
+}
+
+// Converts an array index, which must be an integer. Indexes that are negative,
+// or too large for any array, are None.
+pub fn array_index(value: &Value) -> Result<Option<usize>, EvalError> {
+    let number = match Number::from_value(value) {
+        Some(number) => number,
+        None => {
+            return Err(EvalError::type_error(
+                "number",
+                value,
+                Some(0),
+                "Arguments to arrays must be numbers!".to_string(),
+            ))
+        }
+    };
+    match number.to_integer() {
+        Some(index) => Ok(index.to_usize()),
+        None => Err(EvalError::type_error(
+            "integer",
+            value,
+            Some(0),
+            format!("array indexes must be integers, not {}!", value),
+        )),
+    }
+}
+
+impl<'a> FromOak<'a> for Number {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        Number::from_value(value).ok_or_else(|| conversion_error("number", value))
+    }
+}
+
+impl IntoOak for Number {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(self.into_value())
+    }
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn keeps_integers_exact() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter
+            .eval("(def .'!' /x .(if (= x 1) .1 .(* x (! (+ x -1))))) (! 25)")
+            .unwrap();
+        assert_eq!(result.to_string(), "15511210043330985984000000");
+        let result = interpreter.eval("[(** 2 100) (div 6 3) (div 1 2) (+ 1 0.5)]");
+        assert_eq!(
+            result.unwrap().to_string(),
+            "[1267650600228229401496703205376 2 0.5 1.5]"
+        );
+        let result = interpreter.eval("[(= 1 1.0) (< 1 1.5) (< (** 10 400) 1.5) ([1 2] 1.0)]");
+        assert_eq!(result.unwrap().to_string(), "[true true false 2]");
+
+        let err = interpreter.eval("([1 2] 0.5)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "TypeError: array indexes must be integers, not 0.5! (at 1:1)"
+        );
+        assert_eq!(*interpreter.eval("([1 2] -1)").unwrap(), Value::Null);
+        let err = interpreter.eval("(exit 1.5)").unwrap_err();
+        assert_eq!(err.exit_code(), None);
+        let err = interpreter.eval("(exit (** 2 40))").unwrap_err();
+        assert_eq!(err.exit_code(), None);
+
+        // exact results too large to hold are a LimitError
+        for program in [
+            "(** 2 4294967296)",
+            "(** (** 2 1048576) 1048576)",
+            "(def .'big' (** (** 2 1048576) 33)) (* big big)",
+            "(** (** 3/2 65536) 1048576)",
+        ] {
+            let err = interpreter.eval(program).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::Limit, "{}", program);
+        }
+    }
+
+    #[test]
+    fn computes_exactly_with_rationals_and_decimals() {
+        let mut interpreter = Interpreter::new();
+        let result =
+            interpreter.eval("[(+ 1/3 1/6) (* 2/3 3) (div 1/3 2) (** 2/3 -2) (+ 1/2 0.5)]");
+        assert_eq!(result.unwrap().to_string(), "[1/2 2 1/6 9/4 1]");
+        let result = interpreter.eval("[(+ 0.1d 0.2d) (* 19.99d 3) (div 10.00d 4) (+ 1/2 0.25d)]");
+        assert_eq!(result.unwrap().to_string(), "[0.3 59.97 2.50 3/4]");
+        let result =
+            interpreter.eval("[(= 0.5d 1/2) (< 1/3 0.3333) (decimal 1/3 2) (rational 0.5d)]");
+        assert_eq!(result.unwrap().to_string(), "[true false 0.33 1/2]");
+        assert_eq!(*interpreter.eval("(div 1/2 0)").unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn computes_math_functions() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.eval(
+            "[(- 5 3) (- 2.5d) (- 1/2 1/3) (idiv -7 2) (mod -7 2) (mod 7 -2) (mod 5.5d 2) (mod -1.5 1)]",
+        );
+        assert_eq!(result.unwrap().to_string(), "[2 -2.5 1/6 -4 1 -1 1.5 0.5]");
+        let result = interpreter
+            .eval("[(abs -1.25d) (floor -2.5d) (ceil 1/3) (round 2.5) (round 3.5) (round 7/3)]");
+        assert_eq!(result.unwrap().to_string(), "[1.25 -3 1 2 4 2]");
+        let result = interpreter.eval("[(min 1 2.0) (max 1 1.0) (log 8 2) (exp 0) (< 3.14 pi)]");
+        assert_eq!(result.unwrap().to_string(), "[1 1 3 1 true]");
+        assert_eq!(
+            *interpreter.eval("(- -.5 0.5d)").unwrap(),
+            Value::Number(-1.0)
+        );
+        // results that aren't finite numbers are null
+        let result = interpreter.eval("[(mod 1 0) (idiv 1.0 0.0) (sqrt -1) (asin 2) (log 0)]");
+        assert_eq!(result.unwrap().to_string(), "[Null Null Null Null Null]");
+        let result = interpreter.eval("[(div 1 0) (div 1d 0) (** 0 -1) (** 10.0 400) (+ 1 null)]");
+        assert_eq!(result.unwrap().to_string(), "[Null Null Null Null 1]");
+        // and arguments of the wrong type are errors, with the same message
+        for (program, message) in [
+            (
+                "(+ 'a' 1)",
+                "argument 2 of + must be a string, not a number!",
+            ),
+            (
+                "(+ true 1)",
+                "argument 1 of + must be a number, not a boolean!",
+            ),
+            (
+                "(* 2 'a')",
+                "argument 2 of * must be a number, not a string!",
+            ),
+            (
+                "(- 'a' 1)",
+                "argument 1 of - must be a number, not a string!",
+            ),
+            (
+                "(* 'a' -1)",
+                "argument 2 of * must be a non-negative integer, not a number!",
+            ),
+            (
+                "(* 'a' 1.5)",
+                "argument 2 of * must be a non-negative integer, not a number!",
+            ),
+        ] {
+            let err = interpreter.eval(program).unwrap_err();
+            assert_eq!(err.to_string(), format!("TypeError: {} (at 1:1)", message));
+        }
+        // strings are repeated a whole number of times, up to a limit
+        let result = interpreter.eval("[(* 'ab' 2) (* 'ab' 2.0) (* '' 100000000000000000000)]");
+        assert_eq!(result.unwrap().to_string(), "[\"abab\" \"abab\" \"\"]");
+        let err = interpreter
+            .eval("(* 'ab' 100000000000000000000)")
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Limit);
+    }
+}
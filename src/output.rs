@@ -0,0 +1,120 @@
+use crate::common::*;
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+// Where the output of `print` goes. The default is standard output (or, in the
+// WASM build, the global log_oak function).
+#[derive(Clone)]
+pub struct Output {
+    sink: Sink,
+}
+
+#[derive(Clone)]
+enum Sink {
+    Stdout,
+    Writer(Rc<RefCell<dyn Write>>),
+    Callback(Rc<dyn Fn(&str)>),
+}
+
+impl Output {
+    pub fn stdout() -> Self {
+        Output { sink: Sink::Stdout }
+    }
+
+    // Writes each printed line to the writer, such as a file or a Vec<u8>.
+    pub fn writer<W: Write + 'static>(writer: W) -> Self {
+        Output::shared(Rc::new(RefCell::new(writer)))
+    }
+
+    // Like writer, but the caller keeps a handle to the writer, so it can read
+    // what was written.
+    pub fn shared<W: Write + 'static>(writer: Rc<RefCell<W>>) -> Self {
+        Output {
+            sink: Sink::Writer(writer),
+        }
+    }
+
+    // Calls the callback with each printed line, without the trailing newline.
+    pub fn callback<F: Fn(&str) + 'static>(callback: F) -> Self {
+        Output {
+            sink: Sink::Callback(Rc::new(callback)),
+        }
+    }
+
+    pub fn print(&self, line: &str) -> Result<(), EvalError> {
+        match &self.sink {
+            Sink::Stdout => stdout(line).map_err(write_error),
+            Sink::Writer(writer) => writeln!(writer.borrow_mut(), "{}", line).map_err(write_error),
+            Sink::Callback(callback) => {
+                callback(line);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_error(err: io::Error) -> EvalError {
+    EvalError::host(format!("could not write output: {}", err))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn stdout(line: &str) -> io::Result<()> {
+    writeln!(io::stdout(), "{}", line)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn stdout(line: &str) -> io::Result<()> {
+    crate::js::log_oak(line);
+    Ok(())
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output::stdout()
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sink {
+            Sink::Stdout => write!(f, "Output(stdout)"),
+            Sink::Writer(_) => write!(f, "Output(writer)"),
+            Sink::Callback(_) => write!(f, "Output(callback)"),
+        }
+    }
+}
+
+// ################################################################
+// #                             TESTS                            #
+// ################################################################
+#[cfg(test)]
+mod tests {
+    use super::Output;
+    use crate::interpreter::Interpreter;
+    use crate::Config;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn sends_print_to_the_output() {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let config = Config::new("(print 'hi') (print [1 2])".to_string())
+            .with_output(Output::shared(Rc::clone(&buffer)));
+        config.run().unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.borrow().clone()).unwrap(),
+            "\"hi\"\n[1 2]\n"
+        );
+
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&lines);
+        let mut interpreter = Interpreter::with_output(Output::callback(move |line| {
+            sink.borrow_mut().push(line.to_string())
+        }));
+        interpreter.eval("(print 1) (print true)").unwrap();
+        assert_eq!(*lines.borrow(), vec!["1", "true"]);
+    }
+}
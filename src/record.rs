@@ -0,0 +1,166 @@
+// Records: values with named fields, such as `#(x 1 y 2)`. The fields keep the
+// order they were given in, and a record is never changed; `with` makes a copy
+// with one field replaced.
+//
+// Rust structs are mapped to and from records by implementing IntoOak and FromOak
+// with this type:
+//
+//     impl IntoOak for Point {
+//         fn into_oak(self) -> Rc<Value> {
+//             Record::new().field("x", self.x).field("y", self.y).into_oak()
+//         }
+//     }
+//
+//     impl<'a> FromOak<'a> for Point {
+//         fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+//             let record = Record::from_oak(value)?;
+//             Ok(Point { x: record.get("x")?, y: record.get("y")? })
+//         }
+//     }
+
+use crate::common::*;
+use crate::convert::{conversion_error, FromOak, IntoOak};
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Default)]
+pub struct Record {
+    fields: Vec<(String, Rc<Value>)>,
+}
+
+impl Record {
+    pub fn new() -> Self {
+        Record { fields: Vec::new() }
+    }
+
+    // Adds a field, replacing any field with the same name.
+    pub fn field<T: IntoOak>(mut self, name: &str, value: T) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    pub fn insert<T: IntoOak>(&mut self, name: &str, value: T) {
+        let value = value.into_oak();
+        match self.fields.iter_mut().find(|(field, _)| field == name) {
+            Some(entry) => entry.1 = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+
+    // Converts the named field, which is an error if it is missing (unless T
+    // accepts null, as Option does).
+    pub fn get<T: for<'b> FromOak<'b>>(&self, name: &str) -> Result<T, EvalError> {
+        let null = Rc::new(Value::Null);
+        let value = self.value(name).unwrap_or(&null);
+        T::from_oak(value).map_err(|e| match e.kind {
+            ErrorKind::Type { .. } if self.value(name).is_none() => no_field(name),
+            ErrorKind::Type { expected, .. } => EvalError::type_error(
+                &expected,
+                value,
+                None,
+                format!(
+                    "field {} must be a {}, not a {}!",
+                    name,
+                    expected,
+                    value.type_name()
+                ),
+            ),
+            _ => e,
+        })
+    }
+
+    pub fn value(&self, name: &str) -> Option<&Rc<Value>> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+    }
+
+    // The value of a field, which is an error if there is no such field.
+    pub fn field_value(&self, name: &str) -> Result<Rc<Value>, EvalError> {
+        self.value(name)
+            .map(Rc::clone)
+            .ok_or_else(|| no_field(name))
+    }
+
+    // A copy with the value of an existing field replaced.
+    pub fn with(&self, name: &str, value: Rc<Value>) -> Result<Record, EvalError> {
+        if self.value(name).is_none() {
+            return Err(no_field(name));
+        }
+        let mut record = self.clone();
+        record.insert(name, value);
+        Ok(record)
+    }
+
+    // Field names, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|(field, _)| field.as_str())
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Rc<Value>)> {
+        self.fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+fn no_field(name: &str) -> EvalError {
+    EvalError::host(format!("record has no field {}!", name))
+}
+
+// Records are equal if they have the same fields with equal values, in any
+// order.
+impl PartialEq for Record {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .fields()
+                .all(|(name, value)| other.value(name) == Some(value))
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<String> = self
+            .fields()
+            .map(|(name, value)| format!("{} {}", name, value))
+            .collect();
+        write!(f, "#({})", fields.join(" "))
+    }
+}
+
+// Maps with string keys convert to records too, with their fields in the order
+// of the keys.
+impl<'a> FromOak<'a> for Record {
+    fn from_oak(value: &'a Rc<Value>) -> Result<Self, EvalError> {
+        match &**value {
+            Value::Record(record) => Ok(record.clone()),
+            Value::Map(map) => {
+                let mut record = Record::new();
+                for (key, field) in sorted_entries(map) {
+                    match key.value() {
+                        Value::StringType(name) => record.insert(name, Rc::clone(field)),
+                        _ => return Err(conversion_error("record", value)),
+                    }
+                }
+                Ok(record)
+            }
+            other => Err(conversion_error("record", other)),
+        }
+    }
+}
+
+impl IntoOak for Record {
+    fn into_oak(self) -> Rc<Value> {
+        Rc::new(Value::Record(self))
+    }
+}
@@ -1,449 +1,729 @@
+use crate::capability::{Capabilities, Capability};
 use crate::common::*;
+use crate::convert::IntoOak;
+use crate::decimal::Decimal;
 use crate::expressions::*;
-use crate::util;
-use std::fmt::Debug;
+use crate::host::{self, HostFn, HostFunction};
+use crate::number::Number;
+use crate::output::Output;
+use crate::record::Record;
+use crate::variant::Variant;
+use num_rational::BigRational;
+use num_traits::{One, ToPrimitive, Zero};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
 use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
 
-// NativeExpression struct, used to create stdlib functions more easily.
-
-pub struct NativeExpression<F>
+// Calls f with each index and element of the array, along with the result of
+// the previous call.
+fn fold_array<F>(array: &[Rc<Value>], mut f: F) -> Result<Rc<Value>, EvalError>
 where
-    F: Fn(Rc<Scope>) -> Result<Rc<Value>, EvalError>,
+    F: FnMut(Rc<Value>, Rc<Value>, Rc<Value>) -> Result<Rc<Value>, EvalError>,
 {
-    pub function: F,
+    let mut prev: Rc<Value> = Rc::new(Value::Null);
+    for (index, element) in array.iter().enumerate() {
+        prev = f(index.into_oak(), Rc::clone(element), prev)?;
+    }
+    Ok(prev)
 }
 
-impl<F> NativeExpression<F>
-where
-    F: Fn(Rc<Scope>) -> Result<Rc<Value>, EvalError>,
-{
-    pub fn new(f: F) -> NativeExpression<F> {
-        NativeExpression { function: f }
+fn expect_map<'a>(
+    map: &'a Value,
+    function: &str,
+) -> Result<&'a HashMap<Key, Rc<Value>>, EvalError> {
+    match map {
+        Value::Map(map) => Ok(map),
+        other => Err(EvalError::type_error(
+            "map",
+            other,
+            Some(0),
+            format!("{} takes a map, not a {}!", function, other.type_name()),
+        )),
     }
 }
 
-impl<F> Expression for NativeExpression<F>
-where
-    F: Fn(Rc<Scope>) -> Result<Rc<Value>, EvalError>,
-{
-    fn evaluate(&self, scope: Rc<Scope>, _pipe_val: Rc<Value>) -> Result<Rc<Value>, EvalError> {
-        (self.function)(scope)
+fn expect_record<'a>(record: &'a Value, function: &str) -> Result<&'a Record, EvalError> {
+    match record {
+        Value::Record(record) => Ok(record),
+        other => Err(EvalError::type_error(
+            "record",
+            other,
+            Some(0),
+            format!("{} takes a record, not a {}!", function, other.type_name()),
+        )),
     }
 }
 
-impl<F> Debug for NativeExpression<F>
-where
-    F: Fn(Rc<Scope>) -> Result<Rc<Value>, EvalError>,
-{
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("NativeExpression")
-    }
+fn define<Args, F: HostFn<Args>>(scope: &Scope, name: &str, function: F) {
+    scope.set(name.to_string(), Rc::new(host::function(name, function)));
 }
 
-pub fn insert_stdlib(scope: &mut Scope) {
-    let fns = vec![
-        // Print function
-        (
-            "print",
-            Function {
-                parameters: vec!["input".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    util::log(scope.get("input")?);
-                    Ok(Rc::new(Value::Null))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // def function
-        (
-            "def",
-            Function {
-                parameters: vec!["identifier".to_string(), "value".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let identifier = scope.get("identifier")?;
-                    let value = scope.get("value")?;
+// Defines a builtin that needs the given capability. If it hasn't been granted,
+// a function that fails with a capability error is defined in its place, so
+// that programs using it get a clear error rather than an unbound name.
+fn define_with<Args, F: HostFn<Args>>(
+    scope: &Scope,
+    capabilities: Capabilities,
+    capability: Capability,
+    name: &str,
+    function: F,
+) {
+    if capabilities.allows(capability) {
+        define(scope, name, function);
+        return;
+    }
 
-                    if let Value::Function(id_obj) = &*identifier {
-                        let scope = match &id_obj.closure {
-                            Some(s) => Rc::clone(s),
-                            None => {
-                                return Err(EvalError::new("Scope could not be found!".to_string()))
-                            }
-                        };
+    let parameters: Vec<String> = (0..function.arity()).map(|i| format!("arg{}", i)).collect();
+    let parameters: Vec<&str> = parameters.iter().map(String::as_str).collect();
+    let function_name = name.to_string();
+    let denied = HostFunction::new(&parameters, move |_| {
+        Err(EvalError::capability(&function_name, capability.name()))
+    });
+    scope.set(name.to_string(), Rc::new(denied.named(name).into_value()));
+}
 
-                        match &*id_obj.call(Vec::new())? {
-                            Value::StringType(id_name) => {
-                                scope.set(id_name.to_string(), Rc::clone(&value));
-                                Ok(value)
-                            }
-                            _ => Err(EvalError::new(
-                                "The first argument of def must be a function returning a string!"
-                                    .to_string(),
-                            )),
-                        }
-                    } else {
-                        Err(EvalError::new(
-                            "The first argument of def must be a function returning a string!"
-                                .to_string(),
-                        ))
-                    }
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // Add function
-        (
-            "+",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let v1 = &*scope.get("v1")?;
-                    let v2 = &*scope.get("v2")?;
-                    Ok(v1 + v2)
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // multiply function
-        (
-            "*",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let v1 = &*scope.get("v1")?;
-                    let v2 = &*scope.get("v2")?;
-                    Ok(v1 * v2)
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // div function
-        (
-            "div",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    if let Value::Number(n1) = &*scope.get("v1")? {
-                        if let Value::Number(n2) = &*scope.get("v2")? {
-                            return Ok(Rc::new(Value::Number(n1 / n2)));
-                        }
-                    }
-                    Err(EvalError::new(
-                        "div requires numbers as arguments!".to_string(),
+pub fn insert_stdlib(scope: &mut Scope, output: &Output, capabilities: Capabilities) {
+    // Print function
+    let output = output.clone();
+    define_with(
+        scope,
+        capabilities,
+        Capability::Console,
+        "print",
+        move |input: Rc<Value>| output.print(&input.to_string()),
+    );
+    // def function; if a type is given (`(def .'n' 0 'number')`), the value must
+    // have it
+    define(
+        scope,
+        "def",
+        |identifier: Rc<Value>,
+         value: Rc<Value>,
+         expected: Option<String>|
+         -> Result<Rc<Value>, EvalError> {
+            let id_obj = match &*identifier {
+                Value::Function(id_obj) => id_obj,
+                other => {
+                    return Err(EvalError::type_error(
+                        "function",
+                        other,
+                        Some(0),
+                        "The first argument of def must be a function returning a string!"
+                            .to_string(),
                     ))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // power function
-        (
-            "**",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    if let Value::Number(n1) = &*scope.get("v1")? {
-                        if let Value::Number(n2) = &*scope.get("v2")? {
-                            return Ok(Rc::new(Value::Number(n1.powf(*n2))));
-                        }
-                    }
-                    Err(EvalError::new(
-                        "** requires numbers as arguments!".to_string(),
-                    ))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // sqrt function
-        (
-            "sqrt",
-            Function {
-                parameters: vec!["val".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    if let Value::Number(num) = &*scope.get("val")? {
-                        let result = num.sqrt();
-                        if result.is_nan() {
-                            Ok(Rc::new(Value::Null))
-                        } else {
-                            Ok(Rc::new(Value::Number(result)))
-                        }
-                    } else {
-                        Err(EvalError::new(
-                            "sqrt requires a number as argument!".to_string(),
-                        ))
-                    }
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        (
-            "=",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let v1 = &*scope.get("v1")?;
-                    let v2 = &*scope.get("v2")?;
-                    Ok(Rc::new(Value::Boolean(v1 == v2)))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        (
-            "<",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    if let Value::Number(n1) = &*scope.get("v1")? {
-                        if let Value::Number(n2) = &*scope.get("v2")? {
-                            return Ok(Rc::new(Value::Boolean(n1 < n2)));
-                        }
-                    }
-                    Err(EvalError::new(
-                        "< requires numbers as arguments!".to_string(),
-                    ))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        (
-            ">",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    if let Value::Number(n1) = &*scope.get("v1")? {
-                        if let Value::Number(n2) = &*scope.get("v2")? {
-                            return Ok(Rc::new(Value::Boolean(n1 > n2)));
-                        }
-                    }
-                    Err(EvalError::new(
-                        "> requires numbers as arguments!".to_string(),
-                    ))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        (
-            "<=",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    if let Value::Number(n1) = &*scope.get("v1")? {
-                        if let Value::Number(n2) = &*scope.get("v2")? {
-                            return Ok(Rc::new(Value::Boolean(n1 <= n2)));
+                }
+            };
+            let scope = match &id_obj.closure {
+                Some(s) => Rc::clone(s),
+                None => return Err(EvalError::host("Scope could not be found!".to_string())),
+            };
+
+            match &*id_obj.call(Vec::new())? {
+                Value::StringType(id_name) => {
+                    if let Some(expected) = expected {
+                        let expected = type_name(&expected).ok_or_else(|| {
+                            EvalError::host(format!("def was given an unknown type {}!", expected))
+                        })?;
+                        if !has_type(&value, expected) {
+                            return Err(EvalError::type_error(
+                                expected,
+                                &value,
+                                Some(1),
+                                format!(
+                                    "{} must be a {}, not a {}!",
+                                    id_name,
+                                    expected,
+                                    value.type_name()
+                                ),
+                            ));
                         }
                     }
-                    Err(EvalError::new(
-                        "<= requires numbers as arguments!".to_string(),
-                    ))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        (
-            ">=",
-            Function {
-                parameters: vec!["v1".to_string(), "v2".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    if let Value::Number(n1) = &*scope.get("v1")? {
-                        if let Value::Number(n2) = &*scope.get("v2")? {
-                            return Ok(Rc::new(Value::Boolean(n1 >= n2)));
-                        }
+                    scope.set(id_name.to_string(), Rc::clone(&value));
+                    Ok(value)
+                }
+                other => Err(EvalError::type_error(
+                    "string",
+                    other,
+                    Some(0),
+                    "The first argument of def must be a function returning a string!".to_string(),
+                )),
+            }
+        },
+    );
+    // = is structural, except that functions are equal only to themselves and NaN
+    // to nothing; same is the deep equality map keys use, under which NaN is NaN
+    define(scope, "=", |v1: Rc<Value>, v2: Rc<Value>| v1 == v2);
+    define(scope, "same", |v1: Rc<Value>, v2: Rc<Value>| v1.same(&v2));
+    // comparisons work on any values, in the order of Value::total_cmp: exact
+    // between all kinds of numbers, by code point for strings, element by element
+    // for arrays, and by type for values of different types. They are false for
+    // NaN.
+    define(scope, "<", |v1: Rc<Value>, v2: Rc<Value>| {
+        v1.compare(&v2) == Some(Ordering::Less)
+    });
+    define(scope, ">", |v1: Rc<Value>, v2: Rc<Value>| {
+        v1.compare(&v2) == Some(Ordering::Greater)
+    });
+    define(scope, "<=", |v1: Rc<Value>, v2: Rc<Value>| {
+        matches!(v1.compare(&v2), Some(Ordering::Less | Ordering::Equal))
+    });
+    define(scope, ">=", |v1: Rc<Value>, v2: Rc<Value>| {
+        matches!(v1.compare(&v2), Some(Ordering::Greater | Ordering::Equal))
+    });
+    // compare function; -1, 0 or 1 as the first value comes before, is the same
+    // as or comes after the second, in the same order (but with NaN after all
+    // other numbers, so that any values can be sorted)
+    define(scope, "compare", |v1: Rc<Value>, v2: Rc<Value>| {
+        v1.total_cmp(&v2) as i8
+    });
+    // compareFolded function; compare, ignoring the case of strings
+    define(scope, "compareFolded", |v1: Rc<Value>, v2: Rc<Value>| {
+        v1.compare_folded(&v2) as i8
+    });
+    // if function; calls then or else depending on the condition. The branch
+    // that isn't taken may be omitted.
+    define(
+        scope,
+        "if",
+        |condition: bool, then_block: Rc<Value>, else_block: Rc<Value>| {
+            let (block, position, name) = if condition {
+                (then_block, 1, "then")
+            } else {
+                (else_block, 2, "else")
+            };
+            match &*block {
+                Value::Function(block_obj) => block_obj.call(Vec::new()),
+                other => Err(EvalError::type_error(
+                    "function",
+                    other,
+                    Some(position),
+                    format!("{} block must be a function!", name),
+                )),
+            }
+        },
+    );
+    // and and or functions; each operand is a boolean, or a block returning one,
+    // which is only called if the result depends on it: `(and (> x 0) .(< y x))`
+    define(scope, "and", |v1: Rc<Value>, v2: Rc<Value>| {
+        Ok::<_, EvalError>(logical_operand(&v1, "and", 0)? && logical_operand(&v2, "and", 1)?)
+    });
+    define(scope, "or", |v1: Rc<Value>, v2: Rc<Value>| {
+        Ok::<_, EvalError>(logical_operand(&v1, "or", 0)? || logical_operand(&v2, "or", 1)?)
+    });
+    define(scope, "not", |val: bool| !val);
+    define(scope, "xor", |v1: bool, v2: bool| v1 != v2);
+    // type function
+    define(scope, "type", |obj: Rc<Value>| obj.type_name());
+    // length function; the number of elements in an array, characters in a
+    // string, entries in a map, fields in a record or payload values of a variant
+    define(scope, "length", |val: Rc<Value>| match &*val {
+        Value::Array(elements) => Ok(elements.len()),
+        Value::StringType(st) => Ok(st.chars().count()),
+        Value::Map(map) => Ok(map.len()),
+        Value::Record(record) => Ok(record.len()),
+        Value::Variant(variant) => Ok(variant.payload().len()),
+        other => Err(EvalError::type_error(
+            "array",
+            other,
+            Some(0),
+            format!(
+                "length takes an array, string, map, record or variant, not a {}!",
+                other.type_name()
+            ),
+        )),
+    });
+    // for function; function gets evaluated with arguments index and accumulator
+    define(scope, "for", |array: Vec<Rc<Value>>, function: Function| {
+        fold_array(&array, |index, _, prev| function.call(vec![index, prev]))
+    });
+    // foreach function; function gets evaluated with arguments item and accumulator
+    define(
+        scope,
+        "foreach",
+        |array: Vec<Rc<Value>>, function: Function| {
+            fold_array(&array, |_, element, prev| {
+                function.call(vec![element, prev])
+            })
+        },
+    );
+    // exit function; stops the program by unwinding with an Exit error. The
+    // code is 0 if it is left out.
+    define_with(
+        scope,
+        capabilities,
+        Capability::Exit,
+        "exit",
+        |code: Option<Number>| -> Result<(), EvalError> {
+            let code = match code {
+                Some(code) => match code.to_integer().and_then(|int| int.to_i32()) {
+                    Some(code) => code,
+                    None => {
+                        return Err(EvalError::host(format!(
+                            "exit code must be an integer from {} to {}, not {}!",
+                            i32::MIN,
+                            i32::MAX,
+                            code.into_value()
+                        )))
                     }
-                    Err(EvalError::new(
-                        ">= requires numbers as arguments!".to_string(),
-                    ))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // if function
-        (
-            "if",
-            Function {
-                parameters: vec![
-                    "condition".to_string(),
-                    "then".to_string(),
-                    "else".to_string(),
-                ],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let condition = scope.get("condition")?;
-                    let then_block = scope.get("then")?;
-                    let else_block = scope.get("else")?;
+                },
+                None => 0,
+            };
+            Err(EvalError::exit(code))
+        },
+    );
+    // assert function; fails with the given message unless condition is true
+    define(
+        scope,
+        "assert",
+        |condition: Rc<Value>, message: Rc<Value>| -> Result<Rc<Value>, EvalError> {
+            match (&*condition, &*message) {
+                (Value::Boolean(true), _) => Ok(condition),
+                (_, Value::StringType(message)) => {
+                    Err(EvalError::host(format!("Assertion failed: {}", message)))
+                }
+                _ => Err(EvalError::host("Assertion failed!".to_string())),
+            }
+        },
+    );
+    // findIndex function; returns index of given item in given array
+    define(
+        scope,
+        "findIndex",
+        |array: Vec<Rc<Value>>, item: Rc<Value>| array.iter().position(|element| *element == item),
+    );
+    insert_math(scope);
+    insert_numbers(scope);
+    insert_maps(scope);
+    insert_records(scope);
+    insert_variants(scope);
+    insert_effects(scope, capabilities);
+}
 
-                    if let Value::Boolean(b) = *condition {
-                        if b {
-                            // evaluate then block
-                            if let Value::Function(then_obj) = &*then_block {
-                                then_obj.call(Vec::new())
-                            } else {
-                                Err(EvalError::new("then block must be a function!".to_string()))
-                            }
-                        } else {
-                            // evaluate else block
-                            if let Value::Function(else_obj) = &*else_block {
-                                else_obj.call(Vec::new())
-                            } else {
-                                Err(EvalError::new("else block must be a function!".to_string()))
-                            }
-                        }
-                    } else {
-                        Err(EvalError::new(
-                            "if condition argument must evaluate to boolean!".to_string(),
-                        ))
-                    }
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // type function
-        (
-            "type",
-            Function {
-                parameters: vec!["obj".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let obj = scope.get("obj")?;
+// An operand of and or or, calling it if it is a block. Only booleans count:
+// there is no truthiness, so null, 0 and '' are errors, as they are for if.
+fn logical_operand(operand: &Value, name: &str, position: usize) -> Result<bool, EvalError> {
+    let result = match operand {
+        Value::Function(block) => block.call(Vec::new())?,
+        Value::Boolean(b) => return Ok(*b),
+        other => return Err(not_boolean(other, name, position)),
+    };
+    match &*result {
+        Value::Boolean(b) => Ok(*b),
+        other => Err(not_boolean(other, name, position)),
+    }
+}
 
-                    let type_str = match *obj {
-                        Value::Number(_) => "number",
-                        Value::StringType(_) => "string",
-                        Value::Boolean(_) => "boolean",
-                        Value::Function(_) => "function",
-                        Value::Null => "null",
-                    };
-                    Ok(Rc::new(Value::StringType(type_str.to_string())))
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
+fn not_boolean(value: &Value, name: &str, position: usize) -> EvalError {
+    EvalError::type_error(
+        "boolean",
+        value,
+        Some(position),
+        format!(
+            "{} takes booleans or blocks returning them, not a {}!",
+            name,
+            value.type_name()
         ),
-        // for function; function gets evaluated with arguments index and accumulator
-        (
-            "for",
-            Function {
-                parameters: vec!["array".to_string(), "function".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let array = scope.get("array")?;
-                    let function = scope.get("function")?;
+    )
+}
 
-                    if let Value::Function(arr_obj) = &*array {
-                        if !arr_obj.parameters.contains(&"index".to_string()) {
-                            Err(EvalError::new("for requires a valid array".to_string()))
-                        } else if let Value::Function(fn_obj) = &*function {
-                            let mut index = 0;
-                            let mut prev: Rc<Value> = Rc::new(Value::Null);
-                            loop {
-                                let index_val = Rc::new(Value::Number(index as f64));
-                                let element = arr_obj.call(vec![Rc::clone(&index_val)])?;
-                                if let Value::Null = &*element {
-                                    break;
-                                }
-                                prev = fn_obj.call(vec![index_val, prev])?;
-                                index += 1;
-                            }
-                            Ok(prev)
-                        } else {
-                            Err(EvalError::new(
-                                "for requires a function as the second argument".to_string(),
-                            ))
-                        }
-                    } else {
-                        Err(EvalError::new("for requires a valid array".to_string()))
-                    }
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // foreach function; function gets evaluated with arguments item and accumulator
-        (
-            "foreach",
-            Function {
-                parameters: vec!["array".to_string(), "function".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let array = scope.get("array")?;
-                    let function = scope.get("function")?;
+// Arithmetic and math functions, which all follow the same rules:
+//  - Arguments must be numbers (except that + joins strings, * repeats them,
+//    and null counts as 0 for both), and anything else is a type error.
+//  - Exact numbers stay exact where the result can be (see number.rs), and the
+//    rest work on floats.
+//  - A result that isn't a finite number is null: dividing by zero (with any
+//    kind of number), an argument outside the function's domain, a float that
+//    overflows, or a NaN or infinite argument.
+//  - Results too large to compute, such as huge exact powers, are limit errors.
+fn insert_math(scope: &Scope) {
+    // Add function
+    define(scope, "+", |v1: Rc<Value>, v2: Rc<Value>| {
+        (&*v1 + &*v2).map(finite_value)
+    });
+    // subtract function; with one argument, it negates it
+    define(scope, "-", |v1: Number, v2: Option<Number>| {
+        finite(match v2 {
+            Some(v2) => v1.subtract(&v2),
+            None => v1.negate(),
+        })
+    });
+    // multiply function
+    define(scope, "*", |v1: Rc<Value>, v2: Rc<Value>| {
+        (&*v1 * &*v2).map(finite_value)
+    });
+    // div function; exact unless a float is involved, but see Number::divide
+    define(scope, "div", |v1: Number, v2: Number| {
+        if v2.is_zero() {
+            return Ok(None);
+        }
+        v1.divide(&v2).map(finite)
+    });
+    // idiv and mod functions; the quotient rounded down, and the remainder,
+    // which has the sign of the divisor
+    define(scope, "idiv", |v1: Number, v2: Number| {
+        v1.floor_divide(&v2).and_then(finite)
+    });
+    define(scope, "mod", |v1: Number, v2: Number| {
+        v1.modulo(&v2).and_then(finite)
+    });
+    // power function; exact for exact numbers with an integer exponent
+    define(scope, "**", |v1: Number, v2: Number| {
+        let negative = v2.compare(&Number::Integer(0.into())) == Some(Ordering::Less);
+        if v1.is_zero() && negative {
+            return Ok(None);
+        }
+        v1.power(&v2).map(finite)
+    });
+    define(scope, "abs", |val: Number| finite(val.abs()));
+    // floor, ceil and round functions; exact numbers round to integers, and
+    // round takes ties to the even neighbour
+    define(scope, "floor", |val: Number| finite(val.floor()));
+    define(scope, "ceil", |val: Number| finite(val.ceil()));
+    define(scope, "round", |val: Number| finite(val.round()));
+    // min and max functions; the first is returned if they are equal
+    define(scope, "min", |v1: Number, v2: Number| {
+        match v1.compare(&v2) {
+            Some(Ordering::Greater) => finite(v2),
+            Some(_) => finite(v1),
+            None => None,
+        }
+    });
+    define(scope, "max", |v1: Number, v2: Number| {
+        match v1.compare(&v2) {
+            Some(Ordering::Less) => finite(v2),
+            Some(_) => finite(v1),
+            None => None,
+        }
+    });
+    define_float(scope, "sqrt", f64::sqrt);
+    define_float(scope, "exp", f64::exp);
+    // log function; the natural logarithm, or the logarithm to the given base
+    define(scope, "log", |val: f64, base: Option<f64>| {
+        finite_float(match base {
+            Some(base) => val.log(base),
+            None => val.ln(),
+        })
+    });
+    define_float(scope, "sin", f64::sin);
+    define_float(scope, "cos", f64::cos);
+    define_float(scope, "tan", f64::tan);
+    define_float(scope, "asin", f64::asin);
+    define_float(scope, "acos", f64::acos);
+    define_float(scope, "atan", f64::atan);
+    // atan2 function; the angle of the point (x, y), given y and then x
+    define(scope, "atan2", |y: f64, x: f64| finite_float(y.atan2(x)));
+    scope.set(
+        "pi".to_string(),
+        Rc::new(Value::Number(std::f64::consts::PI)),
+    );
+    scope.set("e".to_string(), Rc::new(Value::Number(std::f64::consts::E)));
+}
 
-                    if let Value::Function(arr_obj) = &*array {
-                        if !arr_obj.parameters.contains(&"index".to_string()) {
-                            Err(EvalError::new("foreach requires a valid array".to_string()))
-                        } else if let Value::Function(fn_obj) = &*function {
-                            let mut index = 0;
-                            let mut prev: Rc<Value> = Rc::new(Value::Null);
-                            loop {
-                                let index_val = Rc::new(Value::Number(index as f64));
-                                let element = arr_obj.call(vec![Rc::clone(&index_val)])?;
-                                if let Value::Null = &*element {
-                                    break;
-                                }
-                                prev = fn_obj.call(vec![element, prev])?;
-                                index += 1;
-                            }
-                            Ok(prev)
-                        } else {
-                            Err(EvalError::new(
-                                "foreach requires a function as the second argument".to_string(),
-                            ))
-                        }
-                    } else {
-                        Err(EvalError::new("foreach requires a valid array".to_string()))
-                    }
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // exit function
-        (
-            "exit",
-            Function {
-                parameters: vec!["code".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let code = scope.get("code")?;
-                    let code = match *code {
-                        Value::Number(n) => n as i32,
-                        _ => 0,
-                    };
-                    util::exit(code)
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-        // findIndex function; returns index of given item in given array
-        (
-            "findIndex",
-            Function {
-                parameters: vec!["array".to_string(), "item".to_string()],
-                body: Rc::new(NativeExpression::new(|scope| {
-                    let array = scope.get("array")?;
-                    let item = scope.get("item")?;
+fn finite(num: Number) -> Option<Number> {
+    match num {
+        Number::Float(float) => finite_float(float).map(Number::Float),
+        exact => Some(exact),
+    }
+}
 
-                    if let Value::Function(arr_obj) = &*array {
-                        let mut index = 0;
-                        loop {
-                            let element =
-                                arr_obj.call(vec![Rc::new(Value::Number(index as f64))])?;
-                            // if element equals item, return index
-                            if element == item {
-                                break Ok(Rc::new(Value::Number(index as f64)));
-                            }
-                            // end of array; didn't find; return null
-                            if let Value::Null = &*element {
-                                break Ok(Rc::new(Value::Null));
-                            }
-                            index += 1;
-                        }
-                    } else {
-                        Err(EvalError::new(
-                            "the first argument of findIndex must be an array!".to_string(),
-                        ))
-                    }
-                })),
-                closure: Some(Rc::new(Scope::new(None))),
-            },
-        ),
-    ];
+fn finite_float(num: f64) -> Option<f64> {
+    Some(num).filter(|num| num.is_finite())
+}
 
-    for (fn_name, fn_obj) in fns {
-        scope.set(fn_name.to_string(), Rc::new(Value::Function(fn_obj)))
+fn finite_value(value: Rc<Value>) -> Rc<Value> {
+    match *value {
+        Value::Number(num) if !num.is_finite() => Rc::new(Value::Null),
+        _ => value,
     }
 }
 
+// Defines a math function of one float, such as sin.
+fn define_float(scope: &Scope, name: &str, function: fn(f64) -> f64) {
+    define(scope, name, move |val: f64| finite_float(function(val)));
+}
+
+// Conversions between kinds of numbers, and to and from strings.
+fn insert_numbers(scope: &Scope) {
+    let not_convertible = |val: &Value, kind: &str| {
+        EvalError::host(format!("{} cannot be converted to a {}!", val, kind))
+    };
+    // int function; converts a number to an integer, rounding towards zero
+    define(scope, "int", move |val: Rc<Value>| {
+        Number::from_value(&val)
+            .and_then(|num| num.to_rational())
+            .map(|rational| rational.trunc().to_integer())
+            .ok_or_else(|| not_convertible(&val, "integer"))
+    });
+    // float function; converts a number to the nearest float
+    define(scope, "float", |val: f64| val);
+    // rational function; the exact value of a number, or of a string such as
+    // '1/3', divided by the denominator if one is given
+    define(
+        scope,
+        "rational",
+        move |val: Rc<Value>, denominator: Option<Number>| {
+            let number = match &*val {
+                Value::StringType(st) => st.trim().parse().ok(),
+                other => Number::from_value(other),
+            };
+            let rational = number
+                .and_then(|num| num.to_rational())
+                .ok_or_else(|| not_convertible(&val, "rational"))?;
+            let denominator = match denominator {
+                Some(num) => num
+                    .to_rational()
+                    .ok_or_else(|| not_convertible(&num.into_value(), "rational"))?,
+                None => BigRational::one(),
+            };
+            if denominator.is_zero() {
+                return Err(EvalError::host(format!(
+                    "{} cannot be divided by zero!",
+                    val
+                )));
+            }
+            Ok(Number::rational(rational / denominator))
+        },
+    );
+    // decimal function; converts a number, or a string such as '19.99', to a
+    // decimal, rounded to the given number of places if they are given (with
+    // ties going to the even neighbour)
+    define(
+        scope,
+        "decimal",
+        move |val: Rc<Value>, places: Option<u32>| {
+            let decimal = match &*val {
+                Value::StringType(st) => st.trim().parse().ok(),
+                other => Number::from_value(other).and_then(|num| num.convert_to_decimal()),
+            };
+            let decimal: Decimal = decimal.ok_or_else(|| not_convertible(&val, "decimal"))?;
+            Ok::<_, EvalError>(match places {
+                Some(places) => decimal.round(places)?,
+                None => decimal,
+            })
+        },
+    );
+    // string function; converts a value to a string, as print shows it but
+    // without quotes around strings
+    define(scope, "string", |val: Rc<Value>| val.to_plain_string());
+}
+
+// Map functions. Maps are never changed: insert and remove return a new map.
+fn insert_maps(scope: &Scope) {
+    // get function; the value for the key, or null if there is none
+    define(scope, "get", |map: Rc<Value>, key: Rc<Value>| {
+        let map = expect_map(&map, "get")?;
+        Ok::<_, EvalError>(map.get(&Key::new(Rc::clone(&key))).map(Rc::clone))
+    });
+    define(scope, "contains", |map: Rc<Value>, key: Rc<Value>| {
+        let map = expect_map(&map, "contains")?;
+        Ok::<_, EvalError>(map.contains_key(&Key::new(Rc::clone(&key))))
+    });
+    define(
+        scope,
+        "insert",
+        |map: Rc<Value>, key: Rc<Value>, value: Rc<Value>| {
+            let mut map = expect_map(&map, "insert")?.clone();
+            map.insert(Key::new(Rc::clone(&key)), value);
+            Ok::<_, EvalError>(Value::Map(map))
+        },
+    );
+    define(scope, "remove", |map: Rc<Value>, key: Rc<Value>| {
+        let mut map = expect_map(&map, "remove")?.clone();
+        map.remove(&Key::new(Rc::clone(&key)));
+        Ok::<_, EvalError>(Value::Map(map))
+    });
+    // keys, values and entries return arrays in the order of the keys
+    define(scope, "keys", |map: Rc<Value>| {
+        let entries = sorted_entries(expect_map(&map, "keys")?);
+        let keys = entries.into_iter().map(|(key, _)| key.to_value());
+        Ok::<_, EvalError>(Value::Array(keys.collect()))
+    });
+    define(scope, "values", |map: Rc<Value>| {
+        let entries = sorted_entries(expect_map(&map, "values")?);
+        let values = entries.into_iter().map(|(_, value)| Rc::clone(value));
+        Ok::<_, EvalError>(Value::Array(values.collect()))
+    });
+    define(scope, "entries", |map: Rc<Value>| {
+        let entries = sorted_entries(expect_map(&map, "entries")?);
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| Rc::new(Value::Array(vec![key.to_value(), Rc::clone(value)])));
+        Ok::<_, EvalError>(Value::Array(entries.collect()))
+    });
+}
+
+// Record functions. Like maps, records are never changed.
+fn insert_records(scope: &Scope) {
+    // with function; a copy of the record with the field set to the value
+    define(
+        scope,
+        "with",
+        |record: Rc<Value>, name: String, value: Rc<Value>| {
+            let record = expect_record(&record, "with")?.with(&name, value)?;
+            Ok::<_, EvalError>(Value::Record(record))
+        },
+    );
+    // fields function; the names of the record's fields, in order
+    define(scope, "fields", |record: Rc<Value>| {
+        let names = expect_record(&record, "fields")?
+            .names()
+            .map(str::to_string);
+        Ok::<_, EvalError>(names.collect::<Vec<_>>())
+    });
+}
+
+// Variant functions; variants are taken apart with match.
+fn insert_variants(scope: &Scope) {
+    // variant function; declares a variant with the tag and number of payload
+    // values, returning its constructor (or the variant, if it has no payload)
+    define(scope, "variant", |tag: String, arity: usize| {
+        Variant::declare(&tag, arity)
+    });
+    // tag function; the tag of a variant
+    define(scope, "tag", |variant: Rc<Value>| match &*variant {
+        Value::Variant(variant) => Ok(variant.tag().to_string()),
+        other => Err(EvalError::type_error(
+            "variant",
+            other,
+            Some(0),
+            format!("tag takes a variant, not a {}!", other.type_name()),
+        )),
+    });
+}
+
+// Builtins that affect or observe the world outside the program, other than
+// print and exit.
+fn insert_effects(scope: &Scope, capabilities: Capabilities) {
+    // input function; reads a line from standard input, returning null at the
+    // end of the input
+    define_with(
+        scope,
+        capabilities,
+        Capability::Console,
+        "input",
+        || -> Result<Option<String>, EvalError> {
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(0) => Ok(None),
+                Ok(_) => Ok(Some(line.trim_end_matches(&['\n', '\r'][..]).to_string())),
+                Err(err) => Err(io_error("input", &err)),
+            }
+        },
+    );
+    // file functions
+    define_with(
+        scope,
+        capabilities,
+        Capability::Filesystem,
+        "readFile",
+        |path: String| fs::read_to_string(&path).map_err(|err| io_error(&path, &err)),
+    );
+    define_with(
+        scope,
+        capabilities,
+        Capability::Filesystem,
+        "writeFile",
+        |path: String, contents: String| {
+            fs::write(&path, contents).map_err(|err| io_error(&path, &err))
+        },
+    );
+    define_with(
+        scope,
+        capabilities,
+        Capability::Filesystem,
+        "fileExists",
+        |path: String| Path::new(&path).exists(),
+    );
+    // env function; the value of an environment variable, or null if it is unset
+    define_with(
+        scope,
+        capabilities,
+        Capability::Environment,
+        "env",
+        |name: String| env::var(name).ok(),
+    );
+    // now function; the current time in seconds since the Unix epoch
+    define_with(scope, capabilities, Capability::Clock, "now", now);
+    // random function; a random number between 0 (inclusive) and 1 (exclusive)
+    let state = Cell::new(seed());
+    define_with(
+        scope,
+        capabilities,
+        Capability::Random,
+        "random",
+        move || {
+            let (value, next) = random(state.get());
+            state.set(next);
+            value
+        },
+    );
+    // exec function; runs a command with an optional array of arguments, returning
+    // its standard output
+    define_with(
+        scope,
+        capabilities,
+        Capability::Process,
+        "exec",
+        |command: String, args: Option<Vec<String>>| -> Result<String, EvalError> {
+            let output = Command::new(&command)
+                .args(args.unwrap_or_default())
+                .output()
+                .map_err(|err| io_error(&command, &err))?;
+            if output.status.success() {
+                Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+            } else {
+                Err(EvalError::host(format!(
+                    "{} failed ({}): {}",
+                    command,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )))
+            }
+        },
+    );
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now() -> f64 {
+    js_sys::Date::now() / 1000.0
+}
+
+// Seeds the random number generator from the clock. This is not meant for
+// cryptography.
+fn seed() -> u64 {
+    (now() * 1e9) as u64 | 1
+}
+
+// One step of the xorshift64* generator, returning a number in [0, 1) and the
+// next state.
+fn random(mut state: u64) -> (f64, u64) {
+    state ^= state >> 12;
+    state ^= state << 25;
+    state ^= state >> 27;
+    let value = state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+    (value as f64 / (1u64 << 53) as f64, state)
+}
+
+fn io_error(subject: &str, err: &io::Error) -> EvalError {
+    EvalError::host(format!("{}: {}", subject, err))
+}
+
 pub fn get_prelude() -> Vec<Rc<dyn Expression>> {
     vec![
         // ordered set mapping function; returns a function that takes elements of
@@ -458,6 +738,7 @@ pub fn get_prelude() -> Vec<Rc<dyn Expression>> {
                 Rc::new(LiteralExpression {
                     value: Rc::new(Value::Function(Function {
                         parameters: vec![],
+                        parameter_types: vec![],
                         body: Rc::new(LiteralExpression {
                             value: Rc::new(Value::StringType("osm".to_string())),
                             closure: false,
@@ -469,9 +750,11 @@ pub fn get_prelude() -> Vec<Rc<dyn Expression>> {
                 Rc::new(LiteralExpression {
                     value: Rc::new(Value::Function(Function {
                         parameters: vec!["arr1".to_string(), "arr2".to_string()],
+                        parameter_types: vec![],
                         body: Rc::new(LiteralExpression {
                             value: Rc::new(Value::Function(Function {
                                 parameters: vec!["item".to_string()],
+                                parameter_types: vec![],
                                 body: Rc::new(FunctionExpression {
                                     function: Rc::new(IdentifierExpression {
                                         name: "arr2".to_string(),
@@ -488,7 +771,9 @@ pub fn get_prelude() -> Vec<Rc<dyn Expression>> {
                                                 name: "item".to_string(),
                                             }),
                                         ],
+                                        location: None,
                                     })],
+                                    location: None,
                                 }),
                                 closure: None,
                             })),
@@ -499,6 +784,7 @@ pub fn get_prelude() -> Vec<Rc<dyn Expression>> {
                     closure: true,
                 }),
             ],
+            location: None,
         }),
     ]
 }
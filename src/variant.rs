@@ -0,0 +1,96 @@
+// Variants: tagged values with positional payloads, such as `(Cons 1 Nil)`.
+// They are declared with the `variant` builtin, which returns a constructor
+// (or, for a variant without a payload, the value itself), and taken apart with
+// `match`:
+//
+//     (def .'Cons' (variant 'Cons' 2))
+//     (def .'Nil' (variant 'Nil' 0))
+//     (match (Cons 1 Nil) (Cons head _) .head Nil .null)
+//
+// Variants are compared by tag and payload, so two declarations with the same
+// tag make interchangeable values.
+
+use crate::common::*;
+use crate::host::HostFunction;
+use std::fmt;
+use std::rc::Rc;
+
+// Variants have at most this many payload values; declaring one with more is a
+// limit error, rather than an attempt to allocate its parameters.
+pub const MAX_ARITY: usize = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    tag: String,
+    payload: Vec<Rc<Value>>,
+}
+
+impl Variant {
+    pub fn new(tag: &str, payload: Vec<Rc<Value>>) -> Self {
+        Variant {
+            tag: tag.to_string(),
+            payload,
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn payload(&self) -> &[Rc<Value>] {
+        &self.payload
+    }
+
+    // What `(variant tag arity)` evaluates to: a function taking the payload, or
+    // the variant itself if it has none.
+    pub fn declare(tag: &str, arity: usize) -> Result<Value, EvalError> {
+        if arity > MAX_ARITY {
+            return Err(EvalError::new(
+                ErrorKind::Limit,
+                format!(
+                    "variants can have at most {} payload values, not {}!",
+                    MAX_ARITY, arity
+                ),
+            ));
+        }
+        if arity == 0 {
+            return Ok(Value::Variant(Variant::new(tag, Vec::new())));
+        }
+        let parameters: Vec<String> = (0..arity).map(|i| format!("arg{}", i)).collect();
+        let parameters: Vec<&str> = parameters.iter().map(String::as_str).collect();
+        let name = tag.to_string();
+        Ok(HostFunction::new(&parameters, move |args| {
+            if args.len() != arity {
+                return Err(EvalError::new(
+                    ErrorKind::Arity {
+                        expected: arity,
+                        actual: args.len(),
+                    },
+                    format!(
+                        "{} takes {} arguments, but was given {}!",
+                        name,
+                        arity,
+                        args.len()
+                    ),
+                ));
+            }
+            let payload = (0..arity)
+                .map(|position| args.get::<Rc<Value>>(position))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Rc::new(Value::Variant(Variant::new(&name, payload))))
+        })
+        .named(tag)
+        .into_value())
+    }
+}
+
+// Shown the way they are constructed: `Nil`, or `(Cons 1 Nil)`.
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.payload.is_empty() {
+            return write!(f, "{}", self.tag);
+        }
+        let payload: Vec<String> = self.payload.iter().map(|v| v.to_string()).collect();
+        write!(f, "({} {})", self.tag, payload.join(" "))
+    }
+}
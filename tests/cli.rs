@@ -0,0 +1,97 @@
+// Runs the oak binary, checking what it prints and the status it exits with.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn oak(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_oak"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the oak binary runs");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn reports_errors_with_exit_statuses() {
+    for (args, status, message) in [
+        (&["-e", "(+ 1"][..], 65, "<expression>:1:1: SyntaxError"),
+        (&["-e", "\n(+ 1 'a')"], 70, "<expression>:2:1: TypeError"),
+        (&["missing.oak"], 66, "oak: missing.oak: file not found"),
+        (
+            &["--allow", "console", "-e", "(exec 'ls')"],
+            77,
+            "CapabilityError",
+        ),
+        (
+            &["--result", "bogus", "-e", "1"],
+            64,
+            "unknown result format bogus",
+        ),
+    ] {
+        let output = oak(args, "");
+        assert_eq!(output.status.code(), Some(status), "{:?}", args);
+        assert!(stderr(&output).contains(message), "{}", stderr(&output));
+        assert_eq!(stdout(&output), "");
+    }
+
+    // exit sets the status, without an error message
+    let output = oak(&["-e", "(print 'bye') (exit 3)"], "");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "\"bye\"\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn formats_the_result() {
+    for (args, printed) in [
+        (&["-e", "'oak'"][..], "Oak - result: \"oak\"\n"),
+        (&["--result", "plain", "-e", "'oak'"], "oak\n"),
+        (&["--result=quoted", "-e", "'oak'"], "\"oak\"\n"),
+        (
+            &["--result", "json", "-e", "['a' 1 null]"],
+            "[\"a\",1,null]\n",
+        ),
+        (&["--result", "none", "-e", "'oak'"], ""),
+        (&["-q", "-e", "'oak'"], ""),
+    ] {
+        let output = oak(args, "");
+        assert_eq!(output.status.code(), Some(0), "{:?}", args);
+        assert_eq!(stdout(&output), printed, "{:?}", args);
+    }
+}
+
+#[test]
+fn passes_arguments_to_the_program() {
+    // -e, and the arguments after the program, as args
+    let output = oak(&["-e", "args", "one", "-x"], "");
+    assert_eq!(stdout(&output), "Oak - result: [\"one\" \"-x\"]\n");
+
+    // - reads the program from stdin
+    let output = oak(&["-q", "-", "a", "b"], "(print (length args))");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "2\n");
+
+    // a file may start with a #! line
+    let path = format!("{}/shebang.oak", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "#!/usr/bin/env oak\n(args 0)\n").unwrap();
+    let output = oak(&["--result", "plain", &path, "first"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "first\n");
+}